@@ -27,9 +27,9 @@ pub async fn handler(uri: Uri) -> Result<impl IntoResponse, StatusCode> {
     };
 
     let asset = Assets::get(&path).ok_or(StatusCode::NOT_FOUND)?;
-    Ok(Response::builder()
+    Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, mime_type)
         .body(Body::from(asset.data))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
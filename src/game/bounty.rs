@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::{game::player::PlayerId, misc::PacketSerialize};
+
+// Growth given per level of the bounty.
+pub const BOUNTY_GROWTH: u16 = 5;
+
+// The longest snake is wanted: whoever kills it grows and scores more the longer its streak
+// of kills was.
+#[derive(Default, Debug)]
+pub struct Bounty {
+    pub leader: Option<PlayerId>,
+    streaks: HashMap<PlayerId, u16>,
+    scores: HashMap<PlayerId, u16>,
+}
+
+impl Bounty {
+    // Bounty collected by the killer, if the victim was wanted.
+    pub fn kill(&mut self, killer: PlayerId, victim: PlayerId) -> Option<u16> {
+        let streak = self.streaks.remove(&victim).unwrap_or(0);
+        let killer_streak = self.streaks.entry(killer).or_default();
+        *killer_streak = killer_streak.saturating_add(1);
+        if self.leader != Some(victim) {
+            return None;
+        }
+        let reward = streak.saturating_add(1);
+        let score = self.scores.entry(killer).or_default();
+        *score = score.saturating_add(reward);
+        Some(reward)
+    }
+
+    pub fn die(&mut self, victim: PlayerId) {
+        self.streaks.remove(&victim);
+    }
+
+    // Mark the longest snake, the current leader keeps the mark on a tie. True when changed.
+    pub fn elect(&mut self, lengths: &[(PlayerId, usize)]) -> bool {
+        let best = lengths.iter().map(|&(_, length)| length).max();
+        let leader = if lengths.len() < 2 {
+            None
+        } else if lengths
+            .iter()
+            .any(|&(id, l)| Some(id) == self.leader && Some(l) == best)
+        {
+            self.leader
+        } else {
+            lengths
+                .iter()
+                .find(|&&(_, length)| Some(length) == best)
+                .map(|&(id, _)| id)
+        };
+        let changed = leader != self.leader;
+        self.leader = leader;
+        changed
+    }
+
+    pub fn forget(&mut self, player_id: PlayerId) {
+        self.streaks.remove(&player_id);
+        self.scores.remove(&player_id);
+        if self.leader == Some(player_id) {
+            self.leader = None;
+        }
+    }
+}
+
+impl PacketSerialize for Bounty {
+    fn push(&self, out: &mut Vec<u8>) {
+        (self.leader.is_some() as u8).push(out);
+        self.leader.unwrap_or(0).push(out);
+        let reward = self
+            .leader
+            .map(|id| {
+                self.streaks
+                    .get(&id)
+                    .copied()
+                    .unwrap_or(0)
+                    .saturating_add(1)
+            })
+            .unwrap_or(0);
+        reward.push(out);
+        for (id, score) in &self.scores {
+            id.push(out);
+            score.push(out);
+        }
+    }
+}
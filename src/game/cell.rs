@@ -1,8 +1,12 @@
-use crate::game::{perk::Perk, player::PlayerId};
+use crate::game::{coordinate::Coord, perk::Perk, player::PlayerId};
 
 #[derive(Clone, Debug)]
 pub enum Cell {
     Empty,
     Occupied(PlayerId),
     Perk(Perk),
+    // Covered by the multi-cell perk whose top left corner is the given cell.
+    PerkPart(Coord),
+    Portal,
+    Wall,
 }
@@ -2,14 +2,35 @@ use std::io::{Cursor, Read};
 
 use byteorder::{ReadBytesExt, BE};
 
-use crate::game::size::Size;
+use crate::game::{
+    coordinate::Wrap, direction::Movement, events::EventKind, handicap::HandicapMode, size::Size,
+    topology::Topology,
+};
 
 pub struct Config {
     pub name: String,
     pub size: Size,
     pub speed: u8,
+    pub movement: Movement,
+    pub topology: Topology,
+    pub portals: Option<u8>,
+    pub hill: Option<u16>,
+    pub fog: Option<u8>,
+    pub sudden_death: Option<u16>,
+    pub handicaps: Option<HandicapMode>,
+    pub bounty: bool,
+    pub events: Option<u16>,
+    pub event_kinds: Vec<EventKind>,
+    pub teams: Option<u8>,
+    pub friendly_fire: bool,
+    pub team_ownership: bool,
+    pub capture_the_flag: bool,
     pub foods: u16,
     pub food_strength: u16,
+    pub corpse_food: Option<u8>,
+    pub critters: Option<u8>,
+    pub boost: bool,
+    pub boost_food: bool,
     pub reserved_food: bool,
     pub perk_spacing: u16,
     pub reverser: bool,
@@ -17,7 +38,20 @@ pub struct Config {
     pub speed_boost: Option<u16>,
     pub food_frenzy: Option<u8>,
     pub mines_trail: Option<u8>,
+    pub hidden_mines: bool,
     pub multi_snake: bool,
+    pub independent_bodies: bool,
+    pub shrink: Option<u8>,
+    pub freeze: Option<u16>,
+    pub invert: Option<u16>,
+    pub magnet: Option<u16>,
+    pub bomb: Option<u8>,
+    pub swapper: bool,
+    pub blackout: Option<u16>,
+    pub slow_others: Option<u16>,
+    pub reverse_others: bool,
+    pub big_food: Option<u8>,
+    pub inventory: bool,
 }
 
 impl Config {
@@ -34,23 +68,92 @@ impl Config {
             height: data.read_u16::<BE>().ok()?,
         };
         let speed = data.read_u8().ok()?;
+        let movement = Movement::try_from(data.read_u8().ok()?).ok()?;
+        let topology = data.read_u8().ok()?;
+        let wrap = Wrap::try_from(data.read_u8().ok()?).ok()?;
+        // Mirrored edges would break the alternation of hexagonal rows.
+        let topology = match (topology, wrap) {
+            (0, _) => Topology::Square(wrap),
+            (1, Wrap::Torus) => Topology::Hexagonal,
+            _ => return None,
+        };
+        let portals = data.read_u8().ok()?;
+        let hill = data.read_u16::<BE>().ok()?;
+        let fog = data.read_u8().ok()?;
+        let sudden_death = data.read_u16::<BE>().ok()?;
+        let handicaps = match data.read_u8().ok()? {
+            0 => None,
+            mode => Some(HandicapMode::try_from(mode).ok()?),
+        };
+        let bounty = data.read_u8().ok()? > 0;
+        let events = data.read_u16::<BE>().ok()?;
+        // One bit per kind of event.
+        let event_kinds = data.read_u8().ok()?;
+        if event_kinds >> EventKind::ALL.len() > 0 {
+            return None;
+        }
+        let event_kinds = EventKind::ALL
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| event_kinds & (1 << i) > 0)
+            .map(|(_, kind)| kind)
+            .collect();
+        let teams = data.read_u8().ok()?;
+        let friendly_fire = data.read_u8().ok()? > 0;
+        let team_ownership = data.read_u8().ok()? > 0;
+        let capture_the_flag = data.read_u8().ok()? > 0;
         let foods = data.read_u16::<BE>().ok()?;
         let food_strength = data.read_u16::<BE>().ok()?;
+        let corpse_food = data.read_u8().ok()?;
+        let critters = data.read_u8().ok()?;
+        let boost = data.read_u8().ok()? > 0;
+        let boost_food = data.read_u8().ok()? > 0;
         let reserved_food = data.read_u8().ok()? > 0;
         let reverser = data.read_u8().ok()? > 0;
         let teleporter = data.read_u8().ok()? > 0;
         let speed_boost = data.read_u16::<BE>().ok()?;
         let food_frenzy = data.read_u8().ok()?;
         let mines_trail = data.read_u8().ok()?;
+        let hidden_mines = data.read_u8().ok()? > 0;
         let multi_snake = data.read_u8().ok()? > 0;
+        let independent_bodies = data.read_u8().ok()? > 0;
+        let shrink = data.read_u8().ok()?;
+        let freeze = data.read_u16::<BE>().ok()?;
+        let invert = data.read_u16::<BE>().ok()?;
+        let magnet = data.read_u16::<BE>().ok()?;
+        let bomb = data.read_u8().ok()?;
+        let swapper = data.read_u8().ok()? > 0;
+        let blackout = data.read_u16::<BE>().ok()?;
+        let slow_others = data.read_u16::<BE>().ok()?;
+        let reverse_others = data.read_u8().ok()? > 0;
+        let big_food = data.read_u8().ok()?;
+        let inventory = data.read_u8().ok()? > 0;
         let perk_spacing = data.read_u16::<BE>().ok()?;
 
         Some(Self {
             name,
             size,
             speed,
+            movement,
+            topology,
+            portals: (portals > 0).then_some(portals),
+            hill: (hill > 0).then_some(hill),
+            fog: (fog > 0).then_some(fog),
+            sudden_death: (sudden_death > 0).then_some(sudden_death),
+            handicaps,
+            bounty,
+            events: (events > 0).then_some(events),
+            event_kinds,
+            teams: (teams > 0).then_some(teams),
+            friendly_fire,
+            team_ownership,
+            capture_the_flag,
             foods,
             food_strength,
+            corpse_food: (corpse_food > 0).then_some(corpse_food),
+            critters: (critters > 0).then_some(critters),
+            boost,
+            boost_food,
             reserved_food,
             perk_spacing,
             reverser,
@@ -58,7 +161,20 @@ impl Config {
             speed_boost: (speed_boost > 0).then_some(speed_boost),
             food_frenzy: (food_frenzy > 0).then_some(food_frenzy),
             mines_trail: (mines_trail > 0).then_some(mines_trail),
+            hidden_mines,
             multi_snake,
+            independent_bodies,
+            shrink: (shrink > 0).then_some(shrink),
+            freeze: (freeze > 0).then_some(freeze),
+            invert: (invert > 0).then_some(invert),
+            magnet: (magnet > 0).then_some(magnet),
+            bomb: (bomb > 0).then_some(bomb),
+            swapper,
+            blackout: (blackout > 0).then_some(blackout),
+            slow_others: (slow_others > 0).then_some(slow_others),
+            reverse_others,
+            big_food: (big_food > 0).then_some(big_food),
+            inventory,
         })
     }
 
@@ -67,8 +183,29 @@ impl Config {
             && (16..=255).contains(&self.size.width)
             && (16..=255).contains(&self.size.height)
             && (1..=50).contains(&self.speed)
+            // Hexagonal rows only wrap around with an even count, and have their own directions.
+            && (self.topology != Topology::Hexagonal
+                || (self.size.height.is_multiple_of(2) && self.movement == Movement::FourWay))
+            && self.portals.map(|c| (1..=8).contains(&c)).unwrap_or(true)
+            && self.hill.map(|p| (20..=2000).contains(&p)).unwrap_or(true)
+            && self.fog.map(|r| (3..=24).contains(&r)).unwrap_or(true)
+            && self
+                .sudden_death
+                .map(|l| (30..=1800).contains(&l))
+                .unwrap_or(true)
+            && self
+                .events
+                .map(|p| (30..=600).contains(&p) && !self.event_kinds.is_empty())
+                .unwrap_or(true)
+            && self.teams.map(|c| (2..=4).contains(&c)).unwrap_or(true)
+            && (!self.capture_the_flag || self.teams.is_some())
             && (1..=32).contains(&self.foods)
             && (0..=1024).contains(&self.food_strength)
+            && self
+                .corpse_food
+                .map(|s| (1..=16).contains(&s))
+                .unwrap_or(true)
+            && self.critters.map(|c| (1..=8).contains(&c)).unwrap_or(true)
             && (1..=128).contains(&self.perk_spacing)
             && self
                 .speed_boost
@@ -82,5 +219,20 @@ impl Config {
                 .mines_trail
                 .map(|c| (1..=16).contains(&c))
                 .unwrap_or(true)
+            && (!self.hidden_mines || self.mines_trail.is_some())
+            && self.shrink.map(|p| (10..=90).contains(&p)).unwrap_or(true)
+            && self.freeze.map(|d| (5..=100).contains(&d)).unwrap_or(true)
+            && self.invert.map(|d| (5..=1000).contains(&d)).unwrap_or(true)
+            && self.magnet.map(|d| (5..=1000).contains(&d)).unwrap_or(true)
+            && self.bomb.map(|r| (2..=16).contains(&r)).unwrap_or(true)
+            && self
+                .blackout
+                .map(|d| (5..=100).contains(&d))
+                .unwrap_or(true)
+            && self
+                .slow_others
+                .map(|d| (5..=1000).contains(&d))
+                .unwrap_or(true)
+            && self.big_food.map(|s| (2..=3).contains(&s)).unwrap_or(true)
     }
 }
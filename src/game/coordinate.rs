@@ -1,4 +1,4 @@
-use std::ops::Add;
+use std::{convert::TryFrom, ops::Add};
 
 use rand::Rng;
 
@@ -21,30 +21,88 @@ impl Coord {
             y: rng.gen_range(0..size.height as usize),
         }
     }
+
+    // Move by the given steps, wrapping around the edges.
+    pub fn shift(self, (dx, dy): (isize, isize), size: Size) -> Self {
+        Coord {
+            x: (self.x as isize + dx).rem_euclid(size.width as isize) as usize,
+            y: (self.y as isize + dy).rem_euclid(size.height as isize) as usize,
+        }
+    }
+
+    // Shortest signed offset to reach `other`, going through edges if needed.
+    pub fn offset(self, other: Coord, size: Size) -> (isize, isize) {
+        fn axis(from: usize, to: usize, length: u16) -> isize {
+            let length = length as isize;
+            let delta = (to as isize - from as isize).rem_euclid(length);
+            if delta > length / 2 {
+                delta - length
+            } else {
+                delta
+            }
+        }
+        (
+            axis(self.x, other.x, size.width),
+            axis(self.y, other.y, size.height),
+        )
+    }
+
+    pub fn distance(self, other: Coord, size: Size) -> usize {
+        let (dx, dy) = self.offset(other, size);
+        dx.unsigned_abs() + dy.unsigned_abs()
+    }
+
+    // Direction of the first step toward `other`, favoring the longest axis.
+    pub fn towards(self, other: Coord, size: Size) -> Option<Dir> {
+        let (dx, dy) = self.offset(other, size);
+        match (dx, dy) {
+            (0, 0) => None,
+            _ if dx.abs() >= dy.abs() => Some(if dx > 0 { Dir::Right } else { Dir::Left }),
+            _ => Some(if dy > 0 { Dir::Down } else { Dir::Up }),
+        }
+    }
 }
 
-impl Add<(Dir, Size)> for Coord {
-    type Output = Self;
-
-    fn add(self, rhs: (Dir, Size)) -> Self::Output {
-        match rhs.0 {
-            Dir::Up => Coord {
-                x: self.x,
-                y: (self.y as isize - 1).rem_euclid(rhs.1.height as isize) as usize,
-            },
-            Dir::Down => Coord {
-                x: self.x,
-                y: (self.y + 1).rem_euclid(rhs.1.height as usize),
-            },
-            Dir::Left => Coord {
-                x: (self.x as isize - 1).rem_euclid(rhs.1.width as isize) as usize,
-                y: self.y,
-            },
-            Dir::Right => Coord {
-                x: (self.x + 1).rem_euclid(rhs.1.width as usize),
-                y: self.y,
-            },
+// Going through an edge of a Klein bottle mirrors the other axis, on a projective plane this
+// happens on both edges. The direction is mirrored along with the position.
+impl Add<(Dir, Size, Wrap)> for Coord {
+    type Output = (Self, Dir);
+
+    fn add(self, (dir, size, wrap): (Dir, Size, Wrap)) -> Self::Output {
+        let (dx, dy) = dir.delta();
+        let crossed_x = !(0..size.width as isize).contains(&(self.x as isize + dx));
+        let crossed_y = !(0..size.height as isize).contains(&(self.y as isize + dy));
+        let mirror_y = crossed_x && wrap != Wrap::Torus;
+        let mirror_x = crossed_y && wrap == Wrap::ProjectivePlane;
+
+        let mut coord = self.shift((dx, dy), size);
+        if mirror_x {
+            coord.x = size.width as usize - 1 - coord.x;
+        }
+        if mirror_y {
+            coord.y = size.height as usize - 1 - coord.y;
         }
+        (coord, dir.mirrored(mirror_x, mirror_y))
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Wrap {
+    Torus,
+    KleinBottle,
+    ProjectivePlane,
+}
+
+impl TryFrom<u8> for Wrap {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Torus,
+            1 => Self::KleinBottle,
+            2 => Self::ProjectivePlane,
+            _ => return Err(()),
+        })
     }
 }
 
@@ -54,3 +112,81 @@ impl PacketSerialize for Coord {
         (self.y as u16).push(out);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Size = Size {
+        width: 8,
+        height: 6,
+    };
+
+    fn coord(x: usize, y: usize) -> Coord {
+        Coord { x, y }
+    }
+
+    #[test]
+    fn crossing_an_edge_wraps_around() {
+        assert_eq!(
+            coord(7, 2) + (Dir::Right, SIZE, Wrap::Torus),
+            (coord(0, 2), Dir::Right)
+        );
+        assert_eq!(
+            coord(3, 0) + (Dir::Up, SIZE, Wrap::Torus),
+            (coord(3, 5), Dir::Up)
+        );
+    }
+
+    #[test]
+    fn klein_bottle_mirrors_across_the_side_edges() {
+        assert_eq!(
+            coord(7, 1) + (Dir::Right, SIZE, Wrap::KleinBottle),
+            (coord(0, 4), Dir::Right)
+        );
+        assert_eq!(
+            coord(0, 1) + (Dir::UpLeft, SIZE, Wrap::KleinBottle),
+            (coord(7, 5), Dir::DownLeft)
+        );
+        assert_eq!(
+            coord(3, 0) + (Dir::Up, SIZE, Wrap::KleinBottle),
+            (coord(3, 5), Dir::Up)
+        );
+    }
+
+    #[test]
+    fn projective_plane_mirrors_across_every_edge() {
+        assert_eq!(
+            coord(7, 1) + (Dir::Right, SIZE, Wrap::ProjectivePlane),
+            (coord(0, 4), Dir::Right)
+        );
+        assert_eq!(
+            coord(2, 0) + (Dir::UpRight, SIZE, Wrap::ProjectivePlane),
+            (coord(4, 5), Dir::UpLeft)
+        );
+    }
+
+    #[test]
+    fn diagonal_corner_crossings() {
+        assert_eq!(
+            coord(7, 0) + (Dir::UpRight, SIZE, Wrap::Torus),
+            (coord(0, 5), Dir::UpRight)
+        );
+        assert_eq!(
+            coord(7, 0) + (Dir::UpRight, SIZE, Wrap::KleinBottle),
+            (coord(0, 0), Dir::DownRight)
+        );
+        assert_eq!(
+            coord(7, 0) + (Dir::UpRight, SIZE, Wrap::ProjectivePlane),
+            (coord(7, 0), Dir::DownLeft)
+        );
+    }
+
+    #[test]
+    fn distance_goes_through_the_edges() {
+        assert_eq!(coord(0, 3).distance(coord(7, 3), SIZE), 1);
+        assert_eq!(coord(2, 0).distance(coord(2, 5), SIZE), 1);
+        assert_eq!(coord(0, 0).distance(coord(7, 5), SIZE), 2);
+        assert_eq!(coord(0, 0).distance(coord(4, 3), SIZE), 7);
+    }
+}
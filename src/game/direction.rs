@@ -8,41 +8,114 @@ pub enum Dir {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl Dir {
+    pub const ALL: [Self; 8] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::UpLeft,
+        Self::UpRight,
+        Self::DownLeft,
+        Self::DownRight,
+    ];
+
     pub fn conflict(&self, other: &Self) -> bool {
-        if self == other {
-            return true;
-        }
-        match (self, other) {
-            (Self::Up, Self::Down) | (Self::Down, Self::Up) => true,
-            (Self::Left, Self::Right) | (Self::Right, Self::Left) => true,
-            _ => false,
-        }
+        self == other || self.opposite() == *other
     }
 
-    #[allow(dead_code)]
     pub fn opposite(&self) -> Self {
         match self {
             Self::Up => Self::Down,
             Self::Down => Self::Up,
             Self::Left => Self::Right,
             Self::Right => Self::Left,
+            Self::UpLeft => Self::DownRight,
+            Self::UpRight => Self::DownLeft,
+            Self::DownLeft => Self::UpRight,
+            Self::DownRight => Self::UpLeft,
+        }
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        matches!(
+            self,
+            Self::UpLeft | Self::UpRight | Self::DownLeft | Self::DownRight
+        )
+    }
+
+    // Horizontal and vertical steps of the direction.
+    pub fn delta(&self) -> (isize, isize) {
+        match self {
+            Self::Up => (0, -1),
+            Self::Down => (0, 1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+            Self::UpLeft => (-1, -1),
+            Self::UpRight => (1, -1),
+            Self::DownLeft => (-1, 1),
+            Self::DownRight => (1, 1),
+        }
+    }
+
+    // Same direction with the horizontal and/or vertical step reversed.
+    pub fn mirrored(&self, horizontal: bool, vertical: bool) -> Self {
+        let (dx, dy) = self.delta();
+        match (
+            if horizontal { -dx } else { dx },
+            if vertical { -dy } else { dy },
+        ) {
+            (0, -1) => Self::Up,
+            (0, 1) => Self::Down,
+            (-1, 0) => Self::Left,
+            (1, 0) => Self::Right,
+            (-1, -1) => Self::UpLeft,
+            (1, -1) => Self::UpRight,
+            (-1, 1) => Self::DownLeft,
+            _ => Self::DownRight,
+        }
+    }
+
+    // Orthogonal directions composing a diagonal one.
+    pub fn components(&self) -> Option<(Self, Self)> {
+        match self {
+            Self::UpLeft => Some((Self::Left, Self::Up)),
+            Self::UpRight => Some((Self::Right, Self::Up)),
+            Self::DownLeft => Some((Self::Left, Self::Down)),
+            Self::DownRight => Some((Self::Right, Self::Down)),
+            _ => None,
         }
     }
 }
 
 impl From<(Coord, Coord)> for Dir {
     fn from((head, body): (Coord, Coord)) -> Self {
-        let x_delta = head.x as isize - body.x as isize;
-        let y_delta = head.y as isize - body.y as isize;
-        match (x_delta, y_delta) {
-            (1 | ..=-2, 0) => Self::Right,
-            (-1 | 2.., 0) => Self::Left,
-            (0, 1 | ..=-2) | (0, 0) => Self::Down,
-            (0, -1 | 2..) => Self::Up,
-            _ => unreachable!(),
+        // Deltas other than -1, 0 and 1 mean that the snake went through an edge.
+        fn step(delta: isize) -> isize {
+            match delta {
+                0 => 0,
+                1 | ..=-2 => 1,
+                -1 | 2.. => -1,
+            }
+        }
+        match (
+            step(head.x as isize - body.x as isize),
+            step(head.y as isize - body.y as isize),
+        ) {
+            (1, 0) => Self::Right,
+            (-1, 0) => Self::Left,
+            (0, -1) => Self::Up,
+            (-1, -1) => Self::UpLeft,
+            (1, -1) => Self::UpRight,
+            (-1, 1) => Self::DownLeft,
+            (1, 1) => Self::DownRight,
+            _ => Self::Down,
         }
     }
 }
@@ -56,6 +129,34 @@ impl TryFrom<u8> for Dir {
             1 => Self::Down,
             2 => Self::Left,
             3 => Self::Right,
+            4 => Self::UpLeft,
+            5 => Self::UpRight,
+            6 => Self::DownLeft,
+            7 => Self::DownRight,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Movement {
+    FourWay,
+    EightWay,
+}
+
+impl Movement {
+    pub fn allows(&self, dir: Dir) -> bool {
+        *self == Self::EightWay || !dir.is_diagonal()
+    }
+}
+
+impl TryFrom<u8> for Movement {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::FourWay,
+            1 => Self::EightWay,
             _ => return Err(()),
         })
     }
@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+use rand::{seq::SliceRandom, thread_rng};
+
+// How long lasting events hold before the world goes back to normal.
+const EVENT_DURATION: Duration = Duration::from_secs(15);
+pub const FOOD_RAIN_COUNT: usize = 24;
+pub const STORM_PORTALS: usize = 4;
+pub const WALLS_COUNT: usize = 6;
+pub const WALL_LENGTH: usize = 6;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum EventKind {
+    // Lots of food at once.
+    FoodRain,
+    // Every snake speeds up.
+    Rush,
+    // Extra portals open for a while.
+    PortalStorm,
+    // Deadly walls rise for a while.
+    Walls,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 4] = [Self::FoodRain, Self::Rush, Self::PortalStorm, Self::Walls];
+
+    // Food rain is over as soon as it has fallen.
+    fn lasts(self) -> bool {
+        self != Self::FoodRain
+    }
+}
+
+// World events picked at random among the enabled ones, one per period.
+#[derive(Debug)]
+pub struct Events {
+    period: Duration,
+    kinds: Vec<EventKind>,
+    last: Instant,
+    active: Option<(EventKind, Instant)>,
+}
+
+impl Events {
+    pub fn new(period: u16, kinds: Vec<EventKind>) -> Self {
+        Self {
+            period: Duration::from_secs(period as u64),
+            kinds,
+            last: Instant::now(),
+            active: None,
+        }
+    }
+
+    // Event starting now, if one is due.
+    pub fn start(&mut self) -> Option<EventKind> {
+        if self.active.is_some() || self.last.elapsed() < self.period {
+            return None;
+        }
+        self.last = Instant::now();
+        let kind = *self.kinds.choose(&mut thread_rng())?;
+        if kind.lasts() {
+            self.active = Some((kind, self.last));
+        }
+        Some(kind)
+    }
+
+    // Lasting event ending now, if its time is up.
+    pub fn end(&mut self) -> Option<EventKind> {
+        let (kind, started) = self.active?;
+        if started.elapsed() < EVENT_DURATION {
+            return None;
+        }
+        self.active = None;
+        Some(kind)
+    }
+
+    pub fn active(&self) -> Option<EventKind> {
+        self.active.map(|(kind, _)| kind)
+    }
+
+    // Seconds left of the active event.
+    pub fn remaining(&self) -> u16 {
+        self.active
+            .map(|(_, started)| EVENT_DURATION.saturating_sub(started.elapsed()).as_secs() as u16)
+            .unwrap_or(0)
+    }
+}
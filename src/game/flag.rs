@@ -0,0 +1,66 @@
+use crate::{
+    game::{
+        coordinate::Coord,
+        player::{BodyId, PlayerId, Team},
+        size::Size,
+    },
+    misc::PacketSerialize,
+};
+
+// Half the side of the square base around a flag's home.
+pub const BASE_RADIUS: usize = 2;
+
+#[derive(Debug)]
+pub struct Flag {
+    pub team: Team,
+    pub home: Coord,
+    pub position: FlagPosition,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum FlagPosition {
+    Ground(Coord),
+    Carried(PlayerId, BodyId),
+}
+
+impl Flag {
+    // Teams are lined up along the middle row.
+    pub fn new(team: Team, teams: u8, size: Size) -> Self {
+        let home = Coord {
+            x: size.width as usize * (2 * team as usize + 1) / (2 * teams as usize),
+            y: size.height as usize / 2,
+        };
+        Self {
+            team,
+            home,
+            position: FlagPosition::Ground(home),
+        }
+    }
+
+    pub fn is_home(&self) -> bool {
+        self.position == FlagPosition::Ground(self.home)
+    }
+
+    pub fn in_base(&self, coord: Coord, size: Size) -> bool {
+        let (dx, dy) = self.home.offset(coord, size);
+        dx.unsigned_abs() <= BASE_RADIUS && dy.unsigned_abs() <= BASE_RADIUS
+    }
+}
+
+impl PacketSerialize for Flag {
+    fn push(&self, out: &mut Vec<u8>) {
+        self.team.push(out);
+        self.home.push(out);
+        match self.position {
+            FlagPosition::Ground(coord) => {
+                0u8.push(out);
+                coord.push(out);
+            }
+            FlagPosition::Carried(player_id, body_id) => {
+                1u8.push(out);
+                player_id.push(out);
+                body_id.push(out);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+const MAX_HANDICAP: u8 = 3;
+const EXTRA_START_SIZE: u16 = 4;
+const FOOD_BONUS_PERCENTAGE: u32 = 25;
+// Deaths older than this no longer count toward handicaps.
+const RESULTS_WINDOW: Duration = Duration::from_secs(120);
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum HandicapMode {
+    // Levels follow the recent deaths of each player.
+    Automatic,
+    // The host sets the level of each player.
+    Host,
+}
+
+impl TryFrom<u8> for HandicapMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::Automatic,
+            2 => Self::Host,
+            _ => return Err(()),
+        })
+    }
+}
+
+// Help given to a struggling player: longer new bodies and more growth from food.
+#[derive(Default, Debug)]
+pub struct Handicap {
+    pub level: u8,
+    deaths: VecDeque<Instant>,
+}
+
+impl Handicap {
+    pub fn record_death(&mut self) -> bool {
+        self.deaths.push_back(Instant::now());
+        self.refresh()
+    }
+
+    // Level from the deaths still in the window, true when it changed.
+    pub fn refresh(&mut self) -> bool {
+        while self
+            .deaths
+            .front()
+            .is_some_and(|d| d.elapsed() > RESULTS_WINDOW)
+        {
+            self.deaths.pop_front();
+        }
+        let level = (self.deaths.len() as u8).min(MAX_HANDICAP);
+        let changed = level != self.level;
+        self.level = level;
+        changed
+    }
+
+    // True when the level changed.
+    pub fn set(&mut self, level: u8) -> bool {
+        let level = level.min(MAX_HANDICAP);
+        let changed = level != self.level;
+        self.level = level;
+        changed
+    }
+
+    pub fn extra_start_size(&self) -> u16 {
+        self.level as u16 * EXTRA_START_SIZE
+    }
+
+    pub fn food(&self, strength: u16) -> u16 {
+        if self.level == 0 {
+            return strength;
+        }
+        let bonus = 100 + self.level as u32 * FOOD_BONUS_PERCENTAGE;
+        (strength as u32 * bonus / 100).min(u16::MAX as u32) as u16
+    }
+}
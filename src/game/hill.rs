@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::{
+    game::{coordinate::Coord, player::PlayerId, size::Size},
+    misc::PacketSerialize,
+};
+
+// Half the side of the square zone.
+pub const HILL_RADIUS: usize = 3;
+
+// King of the hill: the player with the most cells in the zone scores every tick.
+#[derive(Debug)]
+pub struct Hill {
+    pub center: Coord,
+    pub holder: Holder,
+    period: u16,
+    until_move: u16,
+    scores: HashMap<PlayerId, u16>,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Holder {
+    Nobody,
+    Player(PlayerId),
+    Contested,
+}
+
+impl Hill {
+    pub fn new(period: u16, size: Size) -> Self {
+        Self {
+            center: Coord::random(size),
+            holder: Holder::Nobody,
+            period,
+            until_move: period,
+            scores: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, coord: Coord, size: Size) -> bool {
+        let (dx, dy) = self.center.offset(coord, size);
+        dx.unsigned_abs() <= HILL_RADIUS && dy.unsigned_abs() <= HILL_RADIUS
+    }
+
+    // Award the player with the most cells in the zone, then relocate it if it's time.
+    pub fn tick(&mut self, cells: &HashMap<PlayerId, usize>, size: Size) {
+        let best = cells.values().copied().max().unwrap_or(0);
+        let mut leaders = cells.iter().filter(|(_, &c)| c == best && best > 0);
+        self.holder = match (leaders.next(), leaders.next()) {
+            (None, _) => Holder::Nobody,
+            (Some((&id, _)), None) => Holder::Player(id),
+            _ => Holder::Contested,
+        };
+        if let Holder::Player(id) = self.holder {
+            let score = self.scores.entry(id).or_default();
+            *score = score.saturating_add(1);
+        }
+
+        self.until_move -= 1;
+        if self.until_move == 0 {
+            self.until_move = self.period;
+            self.center = Coord::random(size);
+        }
+    }
+
+    pub fn forget(&mut self, player_id: PlayerId) {
+        self.scores.remove(&player_id);
+    }
+}
+
+impl PacketSerialize for Hill {
+    fn push(&self, out: &mut Vec<u8>) {
+        self.center.push(out);
+        (HILL_RADIUS as u8).push(out);
+        let (state, holder) = match self.holder {
+            Holder::Nobody => (0u8, 0),
+            Holder::Player(id) => (1, id),
+            Holder::Contested => (2, 0),
+        };
+        state.push(out);
+        holder.push(out);
+        for (id, score) in &self.scores {
+            id.push(out);
+            score.push(out);
+        }
+    }
+}
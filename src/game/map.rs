@@ -0,0 +1,79 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::game::{
+    coordinate::Coord,
+    direction::{Dir, Movement},
+    size::Size,
+    topology::Topology,
+};
+
+// Layout of the world shared by the game and its players.
+#[derive(Debug)]
+pub struct Map {
+    pub topology: Topology,
+    pub size: Size,
+    // Each portal leads to its pair, both ways.
+    portals: HashMap<Coord, Coord>,
+}
+
+impl Map {
+    pub fn new(topology: Topology, size: Size, pairs: Vec<(Coord, Coord)>) -> Self {
+        Self {
+            topology,
+            size,
+            portals: pairs
+                .into_iter()
+                .flat_map(|(a, b)| [(a, b), (b, a)])
+                .collect(),
+        }
+    }
+
+    // Entering a portal exits next to its pair, keeping the direction.
+    pub fn step(&self, coord: Coord, dir: Dir) -> (Coord, Dir) {
+        let (next, dir) = self.topology.step(coord, dir, self.size);
+        match self.portals.get(&next) {
+            Some(&exit) => self.topology.step(exit, dir, self.size),
+            None => (next, dir),
+        }
+    }
+
+    // Direction of the move that brought a snake from `neck` to `head`, the cells may be
+    // apart when going through an edge or a portal. Cells that no allowed move links, after a
+    // teleport for instance, get the allowed direction leading closest to `head`.
+    pub fn direction(&self, head: Coord, neck: Coord, movement: Movement) -> Dir {
+        Dir::ALL
+            .into_iter()
+            .filter(|&dir| self.topology.allows(dir, movement))
+            .map(|dir| self.step(neck, dir))
+            .min_by_key(|&(coord, _)| self.topology.distance(coord, head, self.size))
+            .map(|(_, dir)| dir)
+            .unwrap()
+    }
+
+    // Cells at most `radius` steps away from `center`, across the edges the way snakes go.
+    pub fn within(&self, center: Coord, radius: usize) -> impl Iterator<Item = Coord> {
+        let mut cells = HashSet::from([center]);
+        let mut frontier = vec![center];
+        for _ in 0..radius {
+            frontier = frontier
+                .into_iter()
+                .flat_map(|coord| {
+                    Dir::ALL
+                        .into_iter()
+                        .filter(|&dir| self.topology.allows(dir, Movement::EightWay))
+                        .map(move |dir| self.topology.step(coord, dir, self.size).0)
+                })
+                .filter(|&coord| cells.insert(coord))
+                .collect();
+        }
+        cells.into_iter()
+    }
+
+    pub fn portals(&self) -> Vec<(Coord, Coord)> {
+        self.portals
+            .iter()
+            .filter(|(a, b)| (a.y, a.x) < (b.y, b.x))
+            .map(|(&a, &b)| (a, b))
+            .collect()
+    }
+}
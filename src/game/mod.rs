@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     iter,
     sync::Arc,
     time::{Duration, Instant},
@@ -11,30 +11,62 @@ use coordinate::Coord;
 use futures::{future::join_all, stream::SplitStream, StreamExt};
 use packet::Packet;
 use player::Player;
+use rand::{seq::SliceRandom, thread_rng};
 use size::Size;
 use tokio::sync::Mutex;
 
 use crate::game::{
+    bounty::{Bounty, BOUNTY_GROWTH},
     config::Config,
+    direction::{Dir, Movement},
+    events::{EventKind, Events, FOOD_RAIN_COUNT, STORM_PORTALS, WALLS_COUNT, WALL_LENGTH},
+    flag::{Flag, FlagPosition},
+    handicap::HandicapMode,
+    hill::Hill,
+    map::Map,
     packet::SnakeChange,
-    perk::{Generator, Perk},
-    player::{BodyId, PlayerId},
+    perk::{Effect, Generator, Perk},
+    player::{BodyCell, BodyId, Color, Effects, PlayerId, Team},
+    round::Round,
+    sight::Sight,
     speed::Speed,
     tick::TickManager,
 };
 
+mod bounty;
 mod cell;
 pub mod config;
 mod coordinate;
 mod direction;
+mod events;
+mod flag;
+mod handicap;
+mod hill;
+mod map;
 mod packet;
 mod perk;
 mod player;
+mod round;
+mod sight;
 mod size;
 mod speed;
 mod tick;
+mod topology;
 
 const EXIT_TIMEOUT: Duration = Duration::from_secs(60);
+const MAGNET_RADIUS: usize = 8;
+const CRITTER_SIGHT: usize = 6;
+const CORPSE_FOOD_STRENGTH_DIVIDER: usize = 10;
+
+// Bodies to kill: whether the head is cleared too, and who killed them.
+type Death = (Arc<Mutex<Player>>, BodyId, bool, Option<PlayerId>);
+
+// Moves of a player's bodies during a tick: removed tail and new head.
+type Walk = (
+    PlayerId,
+    Arc<Mutex<Player>>,
+    Vec<(BodyId, Option<BodyCell>, Coord)>,
+);
 
 #[derive(Debug)]
 pub struct Game {
@@ -46,16 +78,71 @@ pub struct Game {
 
 impl Game {
     pub fn new(config: Config) -> Self {
+        let mut grid =
+            vec![vec![Cell::Empty; config.size.width as usize]; config.size.height as usize];
+        let flags = match (config.capture_the_flag, config.teams) {
+            (true, Some(teams)) => (0..teams)
+                .map(|team| Flag::new(team, teams, config.size))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let mut portals = Vec::new();
+        for _ in 0..config.portals.unwrap_or(0) {
+            let [entrance, exit] = [(); 2].map(|_| {
+                let coord = iter::repeat_with(|| Coord::random(config.size))
+                    .find(|c| {
+                        matches!(grid[c.y][c.x], Cell::Empty) && !flags.iter().any(|f| f.home == *c)
+                    })
+                    .unwrap();
+                grid[coord.y][coord.x] = Cell::Portal;
+                coord
+            });
+            portals.push((entrance, exit));
+        }
+
         let mut inner = Inner {
-            grid: vec![vec![Cell::Empty; config.size.width as usize]; config.size.height as usize],
+            grid,
             players: HashMap::new(),
             perks: HashMap::new(),
             perk_generator: Generator::new(&config),
+            inventory: config.inventory,
+            corpse_food: config.corpse_food,
+            boost: config.boost,
+            boost_food: config.boost_food,
+            hidden_mines: config.hidden_mines,
+            independent_bodies: config.independent_bodies,
+            movement: config.movement,
+            map: Arc::new(Map::new(config.topology, config.size, portals)),
+            critters_resting: false,
+            teams: config.teams,
+            friendly_fire: config.friendly_fire,
+            team_ownership: config.team_ownership,
+            members: HashMap::new(),
+            overlaps: HashMap::new(),
+            team_scores: Vec::new(),
+            flags,
+            captures: vec![0; config.teams.unwrap_or(0) as usize],
+            hill: config.hill.map(|period| Hill::new(period, config.size)),
+            fog: config.fog,
+            sights: HashMap::new(),
+            blind: HashSet::new(),
+            round: config.sudden_death.map(Round::new),
+            handicaps: config.handicaps,
+            host: None,
+            bounty: config.bounty.then(Bounty::default),
+            events: config
+                .events
+                .map(|period| Events::new(period, config.event_kinds)),
+            storm: Vec::new(),
+            walls: Vec::new(),
             last_leave: Instant::now(),
         };
         for _ in 0..(config.foods as usize) {
             inner.add_perk(config.size, inner.perk_generator.respawnable_food());
         }
+        for _ in 0..config.critters.unwrap_or(0) {
+            inner.add_perk(config.size, inner.perk_generator.critter());
+        }
 
         Self {
             name: config.name,
@@ -79,6 +166,11 @@ impl Game {
                 tick_manager.wait_for_join().await;
             } else {
                 let fastest_snake = inner.walk_snakes(self.size, allowed_to_walk).await;
+                if allowed_to_walk == Speed::Normal {
+                    inner.score_hill(self.size).await;
+                }
+                inner.update_round(self.size).await;
+                inner.update_events(self.size).await;
                 drop(inner);
                 allowed_to_walk = tick_manager.sleep(fastest_snake).await;
             }
@@ -91,42 +183,136 @@ impl Game {
         let head = inner.safe_place(self.size);
         let (tx, rx) = socket.split();
         let player_id = rand::random();
-        let (mut player, body_id) = Player::new(player_id, head, tx);
+        let (mut player, body_id) = Player::new(
+            player_id,
+            head,
+            inner.independent_bodies,
+            inner.movement,
+            Arc::clone(&inner.map),
+            tx,
+        );
+        if let Some(team) = inner.smallest_team() {
+            inner.members.insert(player_id, team);
+            player.color = inner.team_color(team);
+        }
         let color = player.color;
+        player.rush(inner.rushing());
         player
-            .send(Packet::Info(self.size, &self.name, player_id).message())
+            .send(Packet::Info(self.size, &self.name, player_id, inner.map.topology).message())
             .await;
 
-        // Add player to game.
-        inner
-            .broadcast_message(Packet::PlayerJoined(player_id, body_id, head, color))
-            .await;
+        // Add player to game, its position stays hidden in the fog of war.
+        if inner.fog.is_some() {
+            inner
+                .broadcast_message(Packet::Snakes(vec![(player_id, &player, false)]))
+                .await;
+        } else {
+            inner
+                .broadcast_message(Packet::PlayerJoined(player_id, body_id, head, color))
+                .await;
+        }
         let player = Arc::new(Mutex::new(player));
         inner.players.insert(player_id, Arc::clone(&player));
+        if inner.handicaps == Some(HandicapMode::Host) && inner.host.is_none() {
+            inner.host = Some(player_id);
+        }
         inner.grid[head.y][head.x] = Cell::Occupied(player_id);
 
         // Send snakes info.
+        let players = join_all(
+            inner
+                .players
+                .iter()
+                .map(|(&id, p)| async move { (id, p.lock().await) }),
+        )
+        .await;
         let snakes_message = Packet::Snakes(
-            join_all(
-                inner
-                    .players
-                    .iter()
-                    .map(|(&id, p)| async move { (id, p.lock().await) }),
-            )
-            .await,
+            players
+                .iter()
+                .map(|(id, p)| (*id, &**p, inner.fog.is_none() || *id == player_id))
+                .collect(),
         )
         .message();
+        drop(players);
+        let effects = join_all(inner.players.iter().map(|(&id, p)| async move {
+            let effects = p.lock().await.effects();
+            (!effects.is_empty()).then_some((id, effects))
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
         let mut player_lock = player.lock().await;
         player_lock.send(snakes_message).await;
 
-        // Send perks info.
-        let perks = inner
-            .perks
-            .iter()
-            .map(|(coord, perk)| (coord.clone(), perk.clone()))
+        // Send perks info, only those in sight with the fog of war.
+        if inner.fog.is_none() {
+            let perks = inner
+                .perks
+                .iter()
+                .filter(|(_, perk)| inner.sees(player_id, perk))
+                .map(|(coord, perk)| (*coord, perk.clone()))
+                .collect::<Vec<_>>();
+            player_lock.send(Packet::Perks(perks).message()).await;
+        }
+
+        // Send portals info.
+        let portals = inner.map.portals();
+        if !portals.is_empty() {
+            player_lock.send(Packet::Portals(portals).message()).await;
+        }
+
+        // Send active effects info.
+        let effects = inner
+            .visible_effects(player_id, &effects)
+            .into_iter()
+            .filter(|(_, effects)| !effects.is_empty())
             .collect::<Vec<_>>();
-        player_lock.send(Packet::Perks(perks).message()).await;
+        if !effects.is_empty() {
+            player_lock.send(Packet::Effects(effects).message()).await;
+        }
         drop(player_lock);
+
+        // Send teams info.
+        if inner.teams.is_some() {
+            inner.broadcast_message(inner.teams_packet()).await;
+            let scores = Packet::TeamScores(inner.team_scores.clone()).message();
+            player.lock().await.send(scores).await;
+        }
+        if !inner.flags.is_empty() {
+            let flags = Packet::Flags(&inner.flags).message();
+            player.lock().await.send(flags).await;
+        }
+        if let Some(hill) = &inner.hill {
+            let hill = Packet::Hill(hill).message();
+            player.lock().await.send(hill).await;
+        }
+        if let Some(round) = &inner.round {
+            let round = Packet::Round(round).message();
+            player.lock().await.send(round).await;
+        }
+        // The host can handicap the new player.
+        if inner.handicaps.is_some() {
+            inner
+                .broadcast_message(inner.handicaps_packet().await)
+                .await;
+        }
+        if let Some(bounty) = &inner.bounty {
+            let bounty = Packet::Bounty(bounty).message();
+            player.lock().await.send(bounty).await;
+        }
+        if !inner.walls.is_empty() {
+            let walls = Packet::Walls(inner.walls.clone()).message();
+            player.lock().await.send(walls).await;
+        }
+        let event = inner.events.as_ref().and_then(|e| {
+            e.active()
+                .map(|kind| Packet::Event(kind, e.remaining()).message())
+        });
+        if let Some(event) = event {
+            player.lock().await.send(event).await;
+        }
+        inner.update_sights(&HashSet::new()).await;
         drop(inner);
 
         // Process events.
@@ -136,16 +322,38 @@ impl Game {
         // Remove and clean player.
         let mut inner = self.inner.lock().await;
         let player = inner.players.remove(&player_id).unwrap();
+        if inner.drop_flag(&mut *player.lock().await) {
+            inner.broadcast_message(Packet::Flags(&inner.flags)).await;
+        }
         for cell in player
             .lock()
             .await
             .bodies_iter()
             .flat_map(|b| b.cells.iter())
         {
-            inner.grid[cell.coord.y][cell.coord.x] = Cell::Empty
+            if inner.leave_cell(player_id, cell.coord) {
+                inner.grid[cell.coord.y][cell.coord.x] = Cell::Empty
+            }
         }
+        inner.sights.remove(&player_id);
+        inner.blind.remove(&player_id);
         inner.last_leave = Instant::now();
         inner.broadcast_message(Packet::PlayerLeft(player_id)).await;
+        if let Some(hill) = &mut inner.hill {
+            hill.forget(player_id);
+        }
+        if let Some(bounty) = &mut inner.bounty {
+            bounty.forget(player_id);
+        }
+        if inner.members.remove(&player_id).is_some() {
+            inner.broadcast_message(inner.teams_packet()).await;
+        }
+        if inner.host == Some(player_id) {
+            inner.host = inner.players.keys().next().copied();
+            inner
+                .broadcast_message(inner.handicaps_packet().await)
+                .await;
+        }
     }
 
     async fn player_loop(&self, player: Arc<Mutex<Player>>, mut rx: SplitStream<WebSocket>) {
@@ -157,13 +365,17 @@ impl Game {
             let Message::Binary(data) = message else {
                 break;
             };
-            let Some(message_id) = data.get(0) else {
+            let Some(message_id) = data.first() else {
                 break;
             };
 
             match message_id {
                 0 => player.lock().await.process_move_event(&data[1..]).await,
                 1 => {
+                    // Colors are those of the teams.
+                    if self.inner.lock().await.teams.is_some() {
+                        continue;
+                    }
                     let (id, new_color) = {
                         let mut player = player.lock().await;
                         (player.id, player.change_color())
@@ -174,6 +386,54 @@ impl Game {
                         .broadcast_message(Packet::ColorChange(id, new_color))
                         .await;
                 }
+                2 => {
+                    let slot = data.get(1).copied().unwrap_or(0) as usize;
+                    self.inner
+                        .lock()
+                        .await
+                        .activate_perk(self.size, &player, slot)
+                        .await;
+                }
+                3 => {
+                    let allowed = self.inner.lock().await.boost;
+                    if allowed {
+                        player
+                            .lock()
+                            .await
+                            .set_boosting(data.get(1).copied().unwrap_or(0) > 0);
+                    }
+                }
+                4 => {
+                    let index = data.get(1).map(|&i| i as usize);
+                    let (id, selected) = {
+                        let mut player = player.lock().await;
+                        (player.id, player.select(index))
+                    };
+                    if let Some(selected) = selected {
+                        self.inner
+                            .lock()
+                            .await
+                            .broadcast_changes(vec![SnakeChange::Select(id, selected)])
+                            .await;
+                    }
+                }
+                5 => {
+                    let Some(&team) = data.get(1) else {
+                        break;
+                    };
+                    self.inner.lock().await.switch_team(&player, team).await;
+                }
+                6 => {
+                    let Some(&[_, high, low, level]) = data.get(..4) else {
+                        break;
+                    };
+                    let target = PlayerId::from_be_bytes([high, low]);
+                    self.inner
+                        .lock()
+                        .await
+                        .set_handicap(&player, target, level)
+                        .await;
+                }
                 _ => break,
             }
         }
@@ -191,23 +451,71 @@ struct Inner {
     players: HashMap<PlayerId, Arc<Mutex<Player>>>,
     perks: HashMap<Coord, Perk>,
     perk_generator: Generator,
+    inventory: bool,
+    corpse_food: Option<u8>,
+    boost: bool,
+    boost_food: bool,
+    // Mines are only seen by their owner and its teammates.
+    hidden_mines: bool,
+    independent_bodies: bool,
+    movement: Movement,
+    map: Arc<Map>,
+    // Critters move every other tick so that they can be caught.
+    critters_resting: bool,
+    teams: Option<u8>,
+    friendly_fire: bool,
+    team_ownership: bool,
+    members: HashMap<PlayerId, Team>,
+    // Cells where a snake lies on a teammate, the grid only holds the first occupant.
+    overlaps: HashMap<Coord, PlayerId>,
+    team_scores: Vec<u16>,
+    // Capture the flag, one flag per team.
+    flags: Vec<Flag>,
+    captures: Vec<u16>,
+    hill: Option<Hill>,
+    // Radius around the heads beyond which players see neither the others nor the perks.
+    fog: Option<u8>,
+    sights: HashMap<PlayerId, Sight>,
+    // Players under a blackout, the board is held back from them until it ends.
+    blind: HashSet<PlayerId>,
+    round: Option<Round>,
+    // Handicapped players get longer snakes and grow more from food.
+    handicaps: Option<HandicapMode>,
+    // Player setting the handicaps, the first to join and then anyone left when they leave.
+    host: Option<PlayerId>,
+    bounty: Option<Bounty>,
+    events: Option<Events>,
+    // Portals and walls raised by the running event.
+    storm: Vec<Coord>,
+    walls: Vec<Coord>,
     last_leave: Instant,
 }
 
 impl Inner {
     // Order:
     // - free all tails
+    // - shed tails of boosting snakes
     // - group next heads by coord
     // - apply heads (queue respawns and perks consuming)
+    // - kill diagonal heads cutting corners
     // - consume perks
-    // - process respawns
+    // - process respawns, dropping the flags of dead carriers
+    // - pick up, return and capture flags
+    // - swap bodies
+    // - pull food toward magnetic heads
+    // - move critters, only to empty cells so they never run into heads
     async fn walk_snakes(&mut self, size: Size, allowed_to_walk: Speed) -> Speed {
         let walks = join_all(self.players.iter().map(|(&id, p)| async move {
             let mut player = p.lock().await;
             if player.speed() < allowed_to_walk {
                 return None;
             }
-            player.walk(size).await.map(|cs| (id, Arc::clone(p), cs))
+            let walk = player.walk().await.map(|cs| (id, Arc::clone(p), cs));
+            // Timed effects wear off once per tick, however fast the snake walks.
+            if allowed_to_walk == Speed::Normal {
+                player.tick();
+            }
+            walk
         }))
         .await
         .into_iter()
@@ -218,18 +526,34 @@ impl Inner {
         let mut perk_consumed = Vec::new();
         let mut changes = Vec::with_capacity(walks.len() * 2);
         let mut new_perks = Vec::new();
+        let mut removed_perks = Vec::new();
+        let mut pending_swaps = Vec::new();
 
         // Free all tails.
         for (player_id, _player, body_changes) in walks.iter() {
             for (body_id, removed, _new) in body_changes {
                 if let Some(removed) = removed {
-                    self.grid[removed.coord.y][removed.coord.x] = Cell::Empty;
                     changes.push(SnakeChange::RemoveTail(*player_id, *body_id));
-                    if let Some(perk) = &removed.perk {
-                        self.grid[removed.coord.y][removed.coord.x] = Cell::Perk(perk.clone());
-                        self.perks.insert(removed.coord, perk.clone());
-                        new_perks.push((removed.coord, perk.clone()));
+                    new_perks.extend(self.free_cell(*player_id, removed));
+                }
+            }
+        }
+
+        // Shed tails of voluntarily boosting snakes.
+        for (player_id, player, _body_changes) in walks.iter() {
+            for (body_id, shed) in player.lock().await.shed() {
+                changes.push(SnakeChange::RemoveTail(*player_id, body_id));
+                // A mine carried by the shed cell is dropped instead of the food.
+                if self.boost_food && shed.perk.is_none() {
+                    if !self.leave_cell(*player_id, shed.coord) {
+                        continue;
                     }
+                    let perk = Perk::new_food(1);
+                    self.grid[shed.coord.y][shed.coord.x] = Cell::Perk(perk.clone());
+                    self.perks.insert(shed.coord, perk.clone());
+                    new_perks.push((shed.coord, perk));
+                } else {
+                    new_perks.extend(self.free_cell(*player_id, &shed));
                 }
             }
         }
@@ -250,35 +574,95 @@ impl Inner {
                             self.grid[new.y][new.x] = Cell::Occupied(*player_id);
                             changes.push(SnakeChange::AddCell(*player_id, *body_id, *new));
                         } else {
-                            need_respawn.push((Arc::clone(player), *body_id, false));
+                            need_respawn.push((Arc::clone(player), *body_id, false, None));
                         }
                     }
-                    Cell::Occupied(_) => {
-                        need_respawn.push((Arc::clone(player), *body_id, false));
+                    // Without friendly fire, snakes go through their teammates.
+                    Cell::Occupied(other)
+                        if !self.friendly_fire
+                            && other != player_id
+                            && self.teammates(*player_id, *other)
+                            && !self.overlaps.contains_key(new)
+                            && collisions[&new] == 1 =>
+                    {
+                        self.overlaps.insert(*new, *player_id);
+                        changes.push(SnakeChange::AddCell(*player_id, *body_id, *new));
                     }
-                    Cell::Perk(perk) => {
+                    Cell::Occupied(other) => {
+                        need_respawn.push((Arc::clone(player), *body_id, false, Some(*other)));
+                    }
+                    // Stepping out of a portal right into another one, or into a wall.
+                    Cell::Portal | Cell::Wall => {
+                        need_respawn.push((Arc::clone(player), *body_id, false, None));
+                    }
+                    // Hitting any cell of a multi-cell perk consumes all of it.
+                    cell @ (Cell::Perk(_) | Cell::PerkPart(_)) => {
                         if collisions[&new] == 1 {
-                            perk_consumed.push((
-                                *player_id,
-                                *body_id,
-                                Arc::clone(player),
-                                perk.clone(),
-                            ));
+                            let anchor = match cell {
+                                Cell::PerkPart(anchor) => *anchor,
+                                _ => *new,
+                            };
+                            if let Some(perk) = self.remove_perk(anchor) {
+                                perk_consumed.push((
+                                    *player_id,
+                                    *body_id,
+                                    Arc::clone(player),
+                                    perk,
+                                ));
+                            }
                             self.grid[new.y][new.x] = Cell::Occupied(*player_id);
-                            self.perks.remove(new);
                             changes.push(SnakeChange::AddCell(*player_id, *body_id, *new));
                         } else {
-                            need_respawn.push((Arc::clone(player), *body_id, false));
+                            need_respawn.push((Arc::clone(player), *body_id, false, None));
                         }
                     }
                 }
             }
         }
 
+        // Kill snakes squeezing diagonally between two occupied cells.
+        if self.movement == Movement::EightWay {
+            for (player_id, player, body_changes) in walks.iter() {
+                let player_lock = player.lock().await;
+                for (body_id, _removed, new) in body_changes {
+                    if !matches!(self.grid[new.y][new.x], Cell::Occupied(id) if id == *player_id) {
+                        // Already dead.
+                        continue;
+                    }
+                    let Some(from) = player_lock
+                        .get_body(*body_id)
+                        .and_then(|b| b.cells.get(1))
+                        .map(|c| c.coord)
+                    else {
+                        continue;
+                    };
+                    if self.cuts_corner(from, *new, size) {
+                        need_respawn.push((Arc::clone(player), *body_id, true, None));
+                    }
+                }
+            }
+        }
+
         // Consume perks and process respawns.
         for (player_id, body_id, player, perk) in perk_consumed {
+            if self.inventory && perk.is_storable() {
+                let mut player = player.lock().await;
+                if player.store(&perk) {
+                    let message = Packet::Inventory(player.inventory()).message();
+                    player.send(message).await;
+                    continue;
+                }
+            }
+
+            let allies = self.allies(player_id);
             let consumption = perk
-                .consume(player_id, body_id, &mut *player.lock().await, &self.perks)
+                .consume(
+                    player_id,
+                    body_id,
+                    &mut *player.lock().await,
+                    &self.perks,
+                    &allies,
+                )
                 .await;
 
             if let Some(change) = consumption.snake_change {
@@ -288,82 +672,1117 @@ impl Inner {
                 }
                 changes.push(change);
             }
+            for (body_id, removed) in consumption.removed_tails {
+                changes.push(SnakeChange::RemoveTail(player_id, body_id));
+                new_perks.extend(self.free_cell(player_id, &removed));
+            }
+            if let Some((center, radius)) = consumption.blast {
+                removed_perks.extend(
+                    self.blast(
+                        player_id,
+                        center,
+                        radius,
+                        size,
+                        &mut changes,
+                        &mut new_perks,
+                        &mut need_respawn,
+                    )
+                    .await,
+                );
+            }
             for perk in consumption.additional_perks {
                 let coord = self.add_perk(size, perk.clone());
                 new_perks.push((coord, perk));
             }
             if let Some(count) = consumption.should_multiply {
-                let mut player_lock = player.lock().await;
-                for _ in 0..count {
-                    let head = self.safe_place(size);
-                    let new_body_id = player_lock.add_body(head);
-                    self.grid[head.y][head.x] = Cell::Occupied(player_id);
-                    changes.push(SnakeChange::AddBody(player_id, new_body_id, head));
+                changes.extend(self.multiply(size, &mut *player.lock().await, count));
+            }
+            if let Some(effect) = consumption.effect_on_others {
+                for (&other_id, other) in self
+                    .players
+                    .iter()
+                    .filter(|(&id, _)| id != player_id && !self.teammates(player_id, id))
+                {
+                    let mut other = other.lock().await;
+                    other.suffer(effect).await;
+                    if let Effect::Reverse = effect {
+                        changes.push(SnakeChange::Reverse(other_id));
+                    }
                 }
             }
+            if consumption.should_swap {
+                pending_swaps.push((player_id, body_id));
+            }
             if consumption.should_die {
-                need_respawn.push((player, body_id, true));
+                need_respawn.push((player, body_id, true, perk.mine_owner()));
             }
 
-            if perk.makes_spawn_food() {
+            if perk.makes_spawn_food() && !self.sudden_death() {
                 for perk in self.perk_generator.next(player_id) {
                     let coord = self.add_perk(size, perk.clone());
                     new_perks.push((coord, perk));
                 }
             }
         }
-        for (player, body_id, clear_head) in need_respawn {
+        let mut flags_moved = false;
+        let mut handicaps_changed = false;
+        let mut bounty_changed = false;
+        let mut rewards = Vec::new();
+        for (player, body_id, clear_head, killer) in need_respawn {
             let mut player = player.lock().await;
-            self.clear_body(&mut player, body_id, clear_head).await;
+            if player.get_body(body_id).is_none() {
+                // Already killed during this tick.
+                continue;
+            }
+            if player
+                .carried_flag()
+                .is_some_and(|(carrier, _)| carrier == body_id)
+            {
+                flags_moved |= self.drop_flag(&mut player);
+            }
+            new_perks.extend(self.clear_body(&mut player, body_id, clear_head).await);
             changes.push(SnakeChange::RemoveBody(player.id, body_id));
-            if player.bodies_len() == 0 {
+            // Only the loss of a player's last body counts as a kill.
+            if let Some(bounty) = self.bounty.as_mut().filter(|_| player.bodies_len() == 0) {
+                match killer.filter(|&killer| killer != player.id) {
+                    Some(killer) => {
+                        rewards.extend(bounty.kill(killer, player.id).map(|r| (killer, r)));
+                        bounty_changed = true;
+                    }
+                    None => bounty.die(player.id),
+                }
+            }
+            if player.bodies_len() == 0 && self.handicaps == Some(HandicapMode::Automatic) {
+                handicaps_changed |= player.handicap.record_death();
+            }
+            if player.bodies_len() == 0 && !self.sudden_death() {
                 let head = self.safe_place(size);
                 let new_body_id = player.add_body(head);
                 self.grid[head.y][head.x] = Cell::Occupied(player.id);
                 changes.push(SnakeChange::AddBody(player.id, new_body_id, head));
             }
+            if player.is_independent() {
+                changes.push(SnakeChange::Select(player.id, player.selected()));
+            }
         }
 
-        if !changes.is_empty() {
-            self.broadcast_message(Packet::SnakeChanges(changes)).await;
+        for (killer, reward) in rewards {
+            if let Some(killer) = self.players.get(&killer) {
+                killer.lock().await.grow(reward * BOUNTY_GROWTH);
+            }
+        }
+
+        if !self.flags.is_empty() {
+            flags_moved |= self.move_flags(size, &walks).await;
         }
-        if !new_perks.is_empty() {
-            self.broadcast_message(Packet::Perks(new_perks)).await;
+
+        for (player_id, body_id) in pending_swaps {
+            changes.extend(self.swap_with_nearest(player_id, body_id, size).await);
         }
 
-        join_all(
+        let mut magnetic_heads = Vec::new();
+        for (_player_id, player, _body_changes) in walks.iter() {
+            magnetic_heads.extend(player.lock().await.heads_if_magnetic());
+        }
+        let mut moved_perks = self.pull_perks(size, &magnetic_heads);
+
+        if allowed_to_walk == Speed::Normal {
+            self.critters_resting = !self.critters_resting;
+            if !self.critters_resting {
+                let mut heads = Vec::new();
+                for player in self.players.values() {
+                    heads.extend(player.lock().await.heads());
+                }
+                moved_perks.extend(self.move_critters(size, &heads));
+            }
+        }
+
+        // Players who just got their sight back miss this tick too, they are sent the whole
+        // board once it is over.
+        let blind = join_all(
             self.players
-                .values()
-                .map(|p| async { p.lock().await.speed() }),
+                .iter()
+                .map(|(&id, p)| async move { p.lock().await.blacked_out().then_some(id) }),
         )
         .await
         .into_iter()
-        .max()
-        .unwrap_or(Speed::Normal)
+        .flatten()
+        .collect::<HashSet<_>>();
+        let cured = self.blind.difference(&blind).copied().collect::<Vec<_>>();
+        self.blind.extend(&blind);
+
+        if self.fog.is_some() {
+            let touched = new_perks
+                .iter()
+                .map(|(coord, _)| *coord)
+                .chain(moved_perks.iter().map(|(_, to)| *to))
+                .collect();
+            self.broadcast_changes(changes).await;
+            self.update_sights(&touched).await;
+        } else {
+            self.broadcast_changes(changes).await;
+            if !new_perks.is_empty() {
+                self.broadcast_perks(new_perks).await;
+            }
+            if !moved_perks.is_empty() {
+                self.broadcast_sighted(Packet::MovePerks(moved_perks)).await;
+            }
+            if !removed_perks.is_empty() {
+                self.broadcast_removed_perks(removed_perks).await;
+            }
+        }
+        if flags_moved {
+            self.broadcast_message(Packet::Flags(&self.flags)).await;
+        }
+
+        let (speeds, effects): (Vec<_>, Vec<_>) =
+            join_all(self.players.iter().map(|(&id, p)| async move {
+                let mut player = p.lock().await;
+                (
+                    player.speed(),
+                    player.take_changed_effects().map(|e| (id, e)),
+                )
+            }))
+            .await
+            .into_iter()
+            .unzip();
+        let effects = effects.into_iter().flatten().collect::<Vec<_>>();
+        if !effects.is_empty() {
+            self.broadcast_effects(effects).await;
+        }
+        self.blind = blind;
+        for id in cured {
+            self.resync(id).await;
+        }
+
+        // A team scores its captured flags, or the total length of its snakes.
+        if let Some(teams) = self.teams {
+            let mut scores = vec![0u16; teams as usize];
+            if !self.flags.is_empty() {
+                scores.clone_from(&self.captures);
+            } else {
+                for (id, player) in self.players.iter() {
+                    if let Some(&team) = self.members.get(id) {
+                        let length = player.lock().await.length() as u16;
+                        scores[team as usize] = scores[team as usize].saturating_add(length);
+                    }
+                }
+            }
+            if scores != self.team_scores {
+                self.team_scores = scores.clone();
+                self.broadcast_message(Packet::TeamScores(scores)).await;
+            }
+        }
+
+        // Handicaps wear off as deaths get older.
+        if self.handicaps == Some(HandicapMode::Automatic) {
+            for player in self.players.values() {
+                handicaps_changed |= player.lock().await.handicap.refresh();
+            }
+        }
+        // The longest snake is wanted.
+        if let Some(mut bounty) = self.bounty.take() {
+            let mut lengths = Vec::with_capacity(self.players.len());
+            for (&id, player) in self.players.iter() {
+                lengths.push((id, player.lock().await.length()));
+            }
+            if bounty.elect(&lengths) || bounty_changed {
+                self.broadcast_message(Packet::Bounty(&bounty)).await;
+            }
+            self.bounty = Some(bounty);
+        }
+
+        if handicaps_changed {
+            self.broadcast_message(self.handicaps_packet().await).await;
+        }
+
+        speeds.into_iter().max().unwrap_or(Speed::Normal)
+    }
+
+    // Enter sudden death when the time is up, and once a single snake is left announce the
+    // winner and bring everyone back for a new round.
+    async fn update_round(&mut self, size: Size) {
+        let Some(mut round) = self.round.take() else {
+            return;
+        };
+        if round.is_due() {
+            // Nobody to fight against.
+            if self.players.len() < 2 {
+                round.restart();
+            } else {
+                round.sudden_death = true;
+                for player in self.players.values() {
+                    player.lock().await.rush(true);
+                }
+            }
+            self.broadcast_message(Packet::Round(&round)).await;
+        } else if round.sudden_death {
+            let mut alive = Vec::new();
+            for (&id, player) in self.players.iter() {
+                if player.lock().await.bodies_len() > 0 {
+                    alive.push(id);
+                }
+            }
+            if alive.len() <= 1 {
+                self.broadcast_message(Packet::RoundOver(alive.first().copied()))
+                    .await;
+                round.restart();
+                let rushing = self.rushing();
+                let mut changes = Vec::new();
+                for player in self.players.values() {
+                    let mut player = player.lock().await;
+                    player.rush(rushing);
+                    if player.bodies_len() == 0 {
+                        let head = self.safe_place(size);
+                        let body_id = player.add_body(head);
+                        self.grid[head.y][head.x] = Cell::Occupied(player.id);
+                        changes.push(SnakeChange::AddBody(player.id, body_id, head));
+                    }
+                }
+                self.broadcast_changes(changes).await;
+                self.broadcast_message(Packet::Round(&round)).await;
+            }
+        }
+        self.round = Some(round);
+    }
+
+    fn sudden_death(&self) -> bool {
+        self.round.as_ref().is_some_and(|r| r.sudden_death)
+    }
+
+    fn rushing(&self) -> bool {
+        self.sudden_death()
+            || self
+                .events
+                .as_ref()
+                .is_some_and(|e| e.active() == Some(EventKind::Rush))
+    }
+
+    // End the running world event once its time is up and start the next one when due.
+    async fn update_events(&mut self, size: Size) {
+        let Some(mut events) = self.events.take() else {
+            return;
+        };
+        if let Some(kind) = events.end() {
+            self.end_event(kind).await;
+            self.broadcast_message(Packet::Event(kind, 0)).await;
+        }
+        if let Some(kind) = events.start() {
+            self.broadcast_message(Packet::Event(kind, events.remaining()))
+                .await;
+            self.start_event(size, kind).await;
+        }
+        self.events = Some(events);
+    }
+
+    async fn start_event(&mut self, size: Size, kind: EventKind) {
+        match kind {
+            EventKind::FoodRain => {
+                let perks = (0..FOOD_RAIN_COUNT)
+                    .map(|_| {
+                        let perk = self.perk_generator.food();
+                        (self.add_perk(size, perk.clone()), perk)
+                    })
+                    .collect();
+                if self.fog.is_some() {
+                    self.update_sights(&HashSet::new()).await;
+                } else {
+                    self.broadcast_perks(perks).await;
+                }
+            }
+            EventKind::Rush => {
+                for player in self.players.values() {
+                    player.lock().await.rush(true);
+                }
+            }
+            EventKind::PortalStorm => {
+                let mut portals = self.map.portals();
+                for _ in 0..STORM_PORTALS {
+                    let [entrance, exit] = [(); 2].map(|_| {
+                        let coord = self.safe_place(size);
+                        self.grid[coord.y][coord.x] = Cell::Portal;
+                        coord
+                    });
+                    self.storm.extend([entrance, exit]);
+                    portals.push((entrance, exit));
+                }
+                self.set_portals(portals).await;
+            }
+            EventKind::Walls => {
+                for _ in 0..WALLS_COUNT {
+                    let start = self.safe_place(size);
+                    let (dx, dy) = if rand::random() { (1, 0) } else { (0, 1) };
+                    // Walls stop at the edges and at anything in the way.
+                    let wall = (0..WALL_LENGTH)
+                        .map(|i| Coord {
+                            x: start.x + i * dx,
+                            y: start.y + i * dy,
+                        })
+                        .take_while(|c| {
+                            c.x < size.width as usize
+                                && c.y < size.height as usize
+                                && self.is_free(*c)
+                        })
+                        .collect::<Vec<_>>();
+                    for coord in wall {
+                        self.grid[coord.y][coord.x] = Cell::Wall;
+                        self.walls.push(coord);
+                    }
+                }
+                self.broadcast_message(Packet::Walls(self.walls.clone()))
+                    .await;
+            }
+        }
+    }
+
+    async fn end_event(&mut self, kind: EventKind) {
+        match kind {
+            EventKind::FoodRain => {}
+            EventKind::Rush => {
+                let rushing = self.rushing();
+                for player in self.players.values() {
+                    player.lock().await.rush(rushing);
+                }
+            }
+            EventKind::PortalStorm => {
+                let storm = std::mem::take(&mut self.storm);
+                for coord in storm.iter() {
+                    self.grid[coord.y][coord.x] = Cell::Empty;
+                }
+                let portals = self
+                    .map
+                    .portals()
+                    .into_iter()
+                    .filter(|(entrance, _)| !storm.contains(entrance))
+                    .collect();
+                self.set_portals(portals).await;
+            }
+            EventKind::Walls => {
+                for coord in std::mem::take(&mut self.walls) {
+                    self.grid[coord.y][coord.x] = Cell::Empty;
+                }
+                self.broadcast_message(Packet::Walls(Vec::new())).await;
+            }
+        }
+    }
+
+    // Replace the map of the game and its players, sending the whole new set of portals.
+    async fn set_portals(&mut self, portals: Vec<(Coord, Coord)>) {
+        let map = Map::new(self.map.topology, self.map.size, portals.clone());
+        self.map = Arc::new(map);
+        for player in self.players.values() {
+            player.lock().await.set_map(Arc::clone(&self.map));
+        }
+        self.broadcast_message(Packet::Portals(portals)).await;
+    }
+
+    // Award the hill to the player with the most cells in it.
+    async fn score_hill(&mut self, size: Size) {
+        let Some(mut hill) = self.hill.take() else {
+            return;
+        };
+        let mut cells = HashMap::new();
+        for (&id, player) in self.players.iter() {
+            let count = player
+                .lock()
+                .await
+                .bodies_iter()
+                .flat_map(|b| b.cells.iter())
+                .filter(|c| hill.contains(c.coord, size))
+                .count();
+            if count > 0 {
+                cells.insert(id, count);
+            }
+        }
+        hill.tick(&cells, size);
+        self.broadcast_message(Packet::Hill(&hill)).await;
+        self.hill = Some(hill);
+    }
+
+    fn multiply(&mut self, size: Size, player: &mut Player, count: u8) -> Vec<SnakeChange> {
+        (0..count)
+            .map(|_| {
+                let head = self.safe_place(size);
+                let new_body_id = player.add_body(head);
+                self.grid[head.y][head.x] = Cell::Occupied(player.id);
+                SnakeChange::AddBody(player.id, new_body_id, head)
+            })
+            .collect()
+    }
+
+    // Consume a perk previously stored in the inventory.
+    async fn activate_perk(&mut self, size: Size, player: &Arc<Mutex<Player>>, slot: usize) {
+        let mut player = player.lock().await;
+        let Some(body_id) = player.bodies_iter().next().map(|b| b.id) else {
+            return;
+        };
+        let Some(perk) = player.take_stored(slot) else {
+            return;
+        };
+        let allies = self.allies(player.id);
+        let consumption = perk
+            .consume(player.id, body_id, &mut player, &self.perks, &allies)
+            .await;
+
+        let mut changes = Vec::new();
+        changes.extend(consumption.snake_change);
+        if let Some(count) = consumption.should_multiply {
+            changes.extend(self.multiply(size, &mut player, count));
+        }
+        let message = Packet::Inventory(player.inventory()).message();
+        player.send(message).await;
+        drop(player);
+
+        self.broadcast_changes(changes).await;
+    }
+
+    // The corner is checked next to the new head, as the move may have gone through a portal
+    // or an edge and left `from` far away.
+    fn cuts_corner(&self, from: Coord, to: Coord, size: Size) -> bool {
+        let Some((horizontal, vertical)) = self.map.direction(to, from, self.movement).components()
+        else {
+            return false;
+        };
+        [horizontal.opposite(), vertical.opposite()]
+            .into_iter()
+            .map(|dir| self.map.topology.step(to, dir, size).0)
+            .all(|c| matches!(self.grid[c.y][c.x], Cell::Occupied(_)))
     }
 
     fn safe_place(&self, size: Size) -> Coord {
+        self.free_square(size, 1)
+    }
+
+    // Top left corner of a square of empty cells, which never wraps around the edges.
+    fn free_square(&self, size: Size, side: usize) -> Coord {
         iter::repeat_with(|| Coord::random(size))
-            .filter(|c| matches!(self.grid[c.y][c.x], Cell::Empty))
-            .next()
+            .filter(|c| c.x + side <= size.width as usize && c.y + side <= size.height as usize)
+            .find(|&c| square(c, side).all(|c| self.is_free(c)))
             .unwrap()
     }
 
+    // Empty cell that is not a flag home, so that nothing is ever placed over one.
+    fn is_free(&self, coord: Coord) -> bool {
+        matches!(self.grid[coord.y][coord.x], Cell::Empty)
+            && !self.flags.iter().any(|f| f.home == coord)
+    }
+
     fn add_perk(&mut self, size: Size, perk: Perk) -> Coord {
-        let coord = self.safe_place(size);
+        let coord = self.free_square(size, perk.side());
+        for part in square(coord, perk.side()).skip(1) {
+            self.grid[part.y][part.x] = Cell::PerkPart(coord);
+        }
         self.grid[coord.y][coord.x] = Cell::Perk(perk.clone());
         self.perks.insert(coord, perk);
         coord
     }
 
-    async fn clear_body(&mut self, player: &mut Player, body_id: BodyId, clear_head: bool) {
+    // Take a perk off the map, emptying all the cells it covers.
+    fn remove_perk(&mut self, coord: Coord) -> Option<Perk> {
+        let perk = self.perks.remove(&coord)?;
+        for part in square(coord, perk.side()) {
+            self.grid[part.y][part.x] = Cell::Empty;
+        }
+        Some(perk)
+    }
+
+    // Cut other snakes at their first cell caught in the blast and destroy mines in range.
+    // Bodies whose head is caught are queued for respawn.
+    #[allow(clippy::too_many_arguments)]
+    async fn blast(
+        &mut self,
+        eater: PlayerId,
+        center: Coord,
+        radius: u8,
+        size: Size,
+        changes: &mut Vec<SnakeChange>,
+        new_perks: &mut Vec<(Coord, Perk)>,
+        need_respawn: &mut Vec<Death>,
+    ) -> Vec<(Coord, Perk)> {
+        let topology = self.map.topology;
+        let in_range = |coord: Coord| topology.distance(coord, center, size) <= radius as usize;
+        let targets = self
+            .players
+            .iter()
+            .filter(|(&id, _)| id != eater)
+            .map(|(&id, p)| (id, Arc::clone(p)))
+            .collect::<Vec<_>>();
+
+        for (player_id, player) in targets {
+            let mut player_lock = player.lock().await;
+            let cuts = player_lock
+                .bodies_iter()
+                .filter_map(|b| Some((b.id, b.cells.iter().position(|c| in_range(c.coord))?)))
+                .collect::<Vec<_>>();
+            for (body_id, position) in cuts {
+                if position == 0 {
+                    need_respawn.push((Arc::clone(&player), body_id, true, Some(eater)));
+                    continue;
+                }
+                for cell in player_lock.truncate_body(body_id, position) {
+                    changes.push(SnakeChange::RemoveTail(player_id, body_id));
+                    if !in_range(cell.coord) {
+                        new_perks.extend(self.free_cell(player_id, &cell));
+                    } else if self.leave_cell(player_id, cell.coord) {
+                        self.grid[cell.coord.y][cell.coord.x] = Cell::Empty;
+                    }
+                }
+            }
+        }
+
+        let mines = self
+            .perks
+            .iter()
+            .filter(|(&coord, perk)| perk.is_mine() && in_range(coord))
+            .map(|(&coord, perk)| (coord, perk.clone()))
+            .collect::<Vec<_>>();
+        for (coord, _) in &mines {
+            self.perks.remove(coord);
+            self.grid[coord.y][coord.x] = Cell::Empty;
+        }
+        mines
+    }
+
+    // Swap a body with the closest body of another player.
+    async fn swap_with_nearest(
+        &mut self,
+        player_id: PlayerId,
+        body_id: BodyId,
+        size: Size,
+    ) -> Option<SnakeChange> {
+        let player = Arc::clone(self.players.get(&player_id)?);
+        let mut player = player.lock().await;
+        let head = player.get_body(body_id)?.cells.front()?.coord;
+
+        let mut nearest = None;
+        for (&other_id, other) in self.players.iter().filter(|(&id, _)| id != player_id) {
+            for other_body in other.lock().await.bodies_iter() {
+                let Some(other_head) = other_body.cells.front() else {
+                    continue;
+                };
+                let distance = self.map.topology.distance(head, other_head.coord, size);
+                if nearest.is_none_or(|(d, _, _)| distance < d) {
+                    nearest = Some((distance, other_id, other_body.id));
+                }
+            }
+        }
+        let (_, other_id, other_body_id) = nearest?;
+
+        let other = Arc::clone(&self.players[&other_id]);
+        let mut other = other.lock().await;
+        if !player.swap_body(body_id, &mut other, other_body_id).await {
+            return None;
+        }
+        // Each swapped cell changes hands where its previous owner was recorded, in the grid or
+        // among the overlaps when that snake was lying on a teammate.
+        let mut handovers = Vec::new();
+        for (id, previous, body) in [
+            (player_id, other_id, player.get_body(body_id)?),
+            (other_id, player_id, other.get_body(other_body_id)?),
+        ] {
+            for cell in &body.cells {
+                let coord = cell.coord;
+                if self.overlaps.get(&coord) == Some(&previous) {
+                    handovers.push((coord, true, id));
+                } else if matches!(self.grid[coord.y][coord.x], Cell::Occupied(o) if o == previous)
+                {
+                    handovers.push((coord, false, id));
+                }
+            }
+        }
+        for (coord, overlap, id) in handovers {
+            if overlap {
+                self.overlaps.insert(coord, id);
+            } else {
+                self.grid[coord.y][coord.x] = Cell::Occupied(id);
+            }
+        }
+        Some(SnakeChange::Swap(
+            player_id,
+            body_id,
+            other_id,
+            other_body_id,
+        ))
+    }
+
+    fn move_perk(&mut self, from: Coord, to: Coord) -> bool {
+        if !matches!(self.grid[to.y][to.x], Cell::Empty) {
+            return false;
+        }
+        let Some(perk) = self.perks.remove(&from) else {
+            return false;
+        };
+        self.grid[from.y][from.x] = Cell::Empty;
+        self.grid[to.y][to.x] = Cell::Perk(perk.clone());
+        self.perks.insert(to, perk);
+        true
+    }
+
+    // Move every food in range one cell toward its closest head.
+    fn pull_perks(&mut self, size: Size, heads: &[Coord]) -> Vec<(Coord, Coord)> {
+        if heads.is_empty() {
+            return Vec::new();
+        }
+        let topology = self.map.topology;
+        let pulls = self
+            .perks
+            .iter()
+            .filter(|(_, perk)| perk.is_food())
+            .filter_map(|(&coord, _)| {
+                let head = heads
+                    .iter()
+                    .filter(|&&h| topology.distance(h, coord, size) <= MAGNET_RADIUS)
+                    .min_by_key(|&&h| topology.distance(h, coord, size))?;
+                let dir = topology.towards(coord, *head, size)?;
+                Some((coord, topology.step(coord, dir, size).0))
+            })
+            .collect::<Vec<_>>();
+        pulls
+            .into_iter()
+            .filter(|&(from, to)| self.move_perk(from, to))
+            .collect()
+    }
+
+    // Move every critter one cell away from the closest head in sight, or randomly.
+    fn move_critters(&mut self, size: Size, heads: &[Coord]) -> Vec<(Coord, Coord)> {
+        let topology = self.map.topology;
+        let critters = self
+            .perks
+            .iter()
+            .filter(|(_, perk)| perk.is_critter())
+            .map(|(&coord, _)| coord)
+            .collect::<Vec<_>>();
+        let mut moves = Vec::new();
+        for coord in critters {
+            let free = Dir::ALL
+                .into_iter()
+                .filter(|&dir| topology.allows(dir, Movement::FourWay))
+                .map(|dir| topology.step(coord, dir, size).0)
+                .filter(|c| matches!(self.grid[c.y][c.x], Cell::Empty))
+                .collect::<Vec<_>>();
+            let threat = heads
+                .iter()
+                .filter(|&&h| topology.distance(h, coord, size) <= CRITTER_SIGHT)
+                .min_by_key(|&&h| topology.distance(h, coord, size));
+            let to = match threat {
+                Some(&head) => free
+                    .into_iter()
+                    .max_by_key(|&c| topology.distance(c, head, size))
+                    .filter(|&c| {
+                        topology.distance(c, head, size) > topology.distance(coord, head, size)
+                    }),
+                None => free.choose(&mut thread_rng()).copied(),
+            };
+            if let Some(to) = to.filter(|&to| self.move_perk(coord, to)) {
+                moves.push((coord, to));
+            }
+        }
+        moves
+    }
+
+    // Empty a cell left by a tail, dropping the perk it may carry.
+    fn free_cell(&mut self, player_id: PlayerId, cell: &BodyCell) -> Option<(Coord, Perk)> {
+        if !self.leave_cell(player_id, cell.coord) {
+            return None;
+        }
+        let Some(perk) = &cell.perk else {
+            self.grid[cell.coord.y][cell.coord.x] = Cell::Empty;
+            return None;
+        };
+        self.grid[cell.coord.y][cell.coord.x] = Cell::Perk(perk.clone());
+        self.perks.insert(cell.coord, perk.clone());
+        Some((cell.coord, perk.clone()))
+    }
+
+    // Whether a cell left by a snake is empty, a teammate may still lie on it.
+    fn leave_cell(&mut self, player_id: PlayerId, coord: Coord) -> bool {
+        let Some(other) = self.overlaps.remove(&coord) else {
+            return true;
+        };
+        if matches!(self.grid[coord.y][coord.x], Cell::Occupied(id) if id == player_id) {
+            self.grid[coord.y][coord.x] = Cell::Occupied(other);
+        }
+        false
+    }
+
+    fn teammates(&self, player_id: PlayerId, other_id: PlayerId) -> bool {
+        matches!(
+            (self.members.get(&player_id), self.members.get(&other_id)),
+            (Some(team), Some(other_team)) if team == other_team
+        )
+    }
+
+    // Players sharing the ownership of reserved foods and mines.
+    fn allies(&self, player_id: PlayerId) -> Vec<PlayerId> {
+        if !self.team_ownership {
+            return vec![player_id];
+        }
+        self.players
+            .keys()
+            .copied()
+            .filter(|&id| id == player_id || self.teammates(player_id, id))
+            .collect()
+    }
+
+    fn team_color(&self, team: Team) -> Color {
+        team as Color * 360 / self.teams.unwrap_or(1) as Color
+    }
+
+    fn smallest_team(&self) -> Option<Team> {
+        (0..self.teams?).min_by_key(|&team| self.members.values().filter(|&&t| t == team).count())
+    }
+
+    async fn switch_team(&mut self, player: &Arc<Mutex<Player>>, team: Team) {
+        if self.teams.is_none_or(|teams| team >= teams) {
+            return;
+        }
+        let color = self.team_color(team);
+        let (id, dropped) = {
+            let mut player = player.lock().await;
+            player.color = color;
+            (player.id, self.drop_flag(&mut player))
+        };
+        if dropped {
+            self.broadcast_message(Packet::Flags(&self.flags)).await;
+        }
+        self.members.insert(id, team);
+        self.broadcast_message(Packet::ColorChange(id, color)).await;
+        self.broadcast_message(self.teams_packet()).await;
+    }
+
+    // Drop the flag carried by a player where the carrying head is.
+    fn drop_flag(&mut self, player: &mut Player) -> bool {
+        let Some((team, head)) = player.release_flag() else {
+            return false;
+        };
+        let flag = &mut self.flags[team as usize];
+        flag.position = FlagPosition::Ground(head.unwrap_or(flag.home));
+        true
+    }
+
+    // Heads touching an enemy flag carry it, their own dropped flag goes back home,
+    // and bringing an enemy flag in their base captures it.
+    async fn move_flags(&mut self, size: Size, walks: &[Walk]) -> bool {
+        let mut moved = false;
+        for (player_id, player, body_changes) in walks {
+            let Some(&team) = self.members.get(player_id) else {
+                continue;
+            };
+            let mut player = player.lock().await;
+            for (body_id, _removed, new) in body_changes {
+                if player
+                    .get_body(*body_id)
+                    .and_then(|b| b.cells.front())
+                    .is_none_or(|c| c.coord != *new)
+                {
+                    // Died during this tick.
+                    continue;
+                }
+
+                for flag in &mut self.flags {
+                    if flag.position != FlagPosition::Ground(*new) {
+                        continue;
+                    }
+                    if flag.team == team {
+                        if !flag.is_home() {
+                            flag.position = FlagPosition::Ground(flag.home);
+                            moved = true;
+                        }
+                    } else if player.carry_flag(*body_id, flag.team) {
+                        flag.position = FlagPosition::Carried(*player_id, *body_id);
+                        moved = true;
+                    }
+                }
+
+                match player.carried_flag() {
+                    Some((carrier, flag_team))
+                        if carrier == *body_id && self.flags[team as usize].in_base(*new, size) =>
+                    {
+                        player.release_flag();
+                        let flag = &mut self.flags[flag_team as usize];
+                        flag.position = FlagPosition::Ground(flag.home);
+                        self.captures[team as usize] += 1;
+                        moved = true;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        moved
+    }
+
+    async fn handicaps_packet(&self) -> Packet<'_> {
+        let mut levels = Vec::with_capacity(self.players.len());
+        for (&id, player) in self.players.iter() {
+            levels.push((id, player.lock().await.handicap.level));
+        }
+        Packet::Handicaps(self.host, levels)
+    }
+
+    async fn set_handicap(&self, player: &Arc<Mutex<Player>>, target: PlayerId, level: u8) {
+        let id = player.lock().await.id;
+        if self.host != Some(id) {
+            return;
+        }
+        let Some(target) = self.players.get(&target) else {
+            return;
+        };
+        if target.lock().await.handicap.set(level) {
+            self.broadcast_message(self.handicaps_packet().await).await;
+        }
+    }
+
+    fn teams_packet(&self) -> Packet<'_> {
+        Packet::Teams(
+            self.team_ownership,
+            self.members.iter().map(|(&id, &team)| (id, team)).collect(),
+        )
+    }
+
+    // Remove a body from the grid, turning it into food if enabled.
+    async fn clear_body(
+        &mut self,
+        player: &mut Player,
+        body_id: BodyId,
+        clear_head: bool,
+    ) -> Vec<(Coord, Perk)> {
         let Some(cells) = player.remove_body(body_id).await else {
+            return Vec::new();
+        };
+        let strength = (cells.len() / CORPSE_FOOD_STRENGTH_DIVIDER).max(1) as u16;
+        let mut foods = Vec::new();
+        for (i, cell) in cells.into_iter().skip(!clear_head as usize).enumerate() {
+            if !self.leave_cell(player.id, cell.coord) {
+                continue;
+            }
+            match self.corpse_food {
+                Some(spacing) if i % spacing as usize == 0 => {
+                    let perk = Perk::new_food(strength);
+                    self.grid[cell.coord.y][cell.coord.x] = Cell::Perk(perk.clone());
+                    self.perks.insert(cell.coord, perk.clone());
+                    foods.push((cell.coord, perk));
+                }
+                _ => self.grid[cell.coord.y][cell.coord.x] = Cell::Empty,
+            }
+        }
+        foods
+    }
+
+    // With the fog of war or under a blackout, players only follow their own snakes and get
+    // their bodies again after a swap, the others are seen through their sight.
+    async fn broadcast_changes(&self, changes: Vec<SnakeChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        if self.fog.is_none() && self.blind.is_empty() {
+            self.broadcast_message(Packet::SnakeChanges(changes)).await;
+            return;
+        }
+        let message = Packet::SnakeChanges(changes.clone()).message();
+        join_all(self.players.iter().map(|(&id, p)| {
+            let changes = &changes;
+            let sighted = self.fog.is_none() && !self.blind.contains(&id);
+            let message = message.clone();
+            async move {
+                if sighted {
+                    p.lock().await.send(message).await;
+                    return;
+                }
+                let own = changes
+                    .iter()
+                    .filter(|c| c.player_id() == id && !matches!(c, SnakeChange::Swap(..)))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let swapped = changes
+                    .iter()
+                    .any(|c| matches!(c, SnakeChange::Swap(a, _, b, _) if *a == id || *b == id));
+                let mut player = p.lock().await;
+                if !own.is_empty() {
+                    player.send(Packet::SnakeChanges(own).message()).await;
+                }
+                if swapped {
+                    let message = Packet::Snakes(vec![(id, &*player, true)]).message();
+                    player.send(message).await;
+                }
+            }
+        }))
+        .await;
+    }
+
+    // Send each player what entered and left the radius around its heads. Removed perks go
+    // first and new ones last so that cells changing kind are drawn right.
+    async fn update_sights(&mut self, touched: &HashSet<Coord>) {
+        let Some(radius) = self.fog else {
             return;
         };
-        cells
-            .into_iter()
-            .skip(!clear_head as usize)
-            .for_each(|c| self.grid[c.coord.y][c.coord.x] = Cell::Empty);
+        let mut sights = std::mem::take(&mut self.sights);
+        for (&id, player) in self
+            .players
+            .iter()
+            .filter(|(id, _)| !self.blind.contains(id))
+        {
+            let mut player = player.lock().await;
+            let visible = player
+                .heads()
+                .into_iter()
+                .flat_map(|head| self.map.within(head, radius as usize))
+                .collect();
+            let update =
+                sights
+                    .entry(id)
+                    .or_default()
+                    .update(id, &visible, &self.grid, touched, |perk| {
+                        self.sees(id, perk)
+                    });
+            if !update.hidden_perks.is_empty() {
+                let message = Packet::RemovePerks(update.hidden_perks).message();
+                player.send(message).await;
+            }
+            if !update.hidden_cells.is_empty() || !update.shown_cells.is_empty() {
+                let message = Packet::Sight(update.hidden_cells, update.shown_cells).message();
+                player.send(message).await;
+            }
+            if !update.shown_perks.is_empty() {
+                let message = Packet::Perks(update.shown_perks).message();
+                player.send(message).await;
+            }
+        }
+        self.sights = sights;
+    }
+
+    fn sees(&self, viewer: PlayerId, perk: &Perk) -> bool {
+        match perk.mine_owner() {
+            Some(owner) if self.hidden_mines => owner == viewer || self.teammates(viewer, owner),
+            _ => true,
+        }
+    }
+
+    // Hidden mines are left out of the perks sent to the other players.
+    async fn broadcast_perks(&self, perks: Vec<(Coord, Perk)>) {
+        if !self.hidden_mines || !perks.iter().any(|(_, perk)| perk.is_mine()) {
+            self.broadcast_sighted(Packet::Perks(perks)).await;
+            return;
+        }
+        join_all(self.sighted_players().map(|(&id, p)| {
+            let perks = perks
+                .iter()
+                .filter(|(_, perk)| self.sees(id, perk))
+                .cloned()
+                .collect::<Vec<_>>();
+            async move {
+                if !perks.is_empty() {
+                    p.lock().await.send(Packet::Perks(perks).message()).await;
+                }
+            }
+        }))
+        .await;
+    }
+
+    // Effects as seen by a player, without the mines of the others when they are hidden.
+    fn visible_effects(
+        &self,
+        viewer: PlayerId,
+        effects: &[(PlayerId, Effects)],
+    ) -> Vec<(PlayerId, Effects)> {
+        effects
+            .iter()
+            .map(|&(id, effects)| {
+                if self.hidden_mines && id != viewer && !self.teammates(viewer, id) {
+                    (id, effects.without_mines())
+                } else {
+                    (id, effects)
+                }
+            })
+            .collect()
+    }
+
+    async fn broadcast_effects(&self, effects: Vec<(PlayerId, Effects)>) {
+        if !self.hidden_mines {
+            self.broadcast_message(Packet::Effects(effects)).await;
+            return;
+        }
+        join_all(self.players.iter().map(|(&id, p)| {
+            let message = Packet::Effects(self.visible_effects(id, &effects)).message();
+            async move {
+                p.lock().await.send(message).await;
+            }
+        }))
+        .await;
+    }
+
+    // Hidden mines are only removed for the players who were sent them.
+    async fn broadcast_removed_perks(&self, perks: Vec<(Coord, Perk)>) {
+        join_all(self.sighted_players().map(|(&id, p)| {
+            let coords = perks
+                .iter()
+                .filter(|(_, perk)| self.sees(id, perk))
+                .map(|(coord, _)| *coord)
+                .collect::<Vec<_>>();
+            async move {
+                if !coords.is_empty() {
+                    p.lock()
+                        .await
+                        .send(Packet::RemovePerks(coords).message())
+                        .await;
+                }
+            }
+        }))
+        .await;
+    }
+
+    fn sighted_players(&self) -> impl Iterator<Item = (&PlayerId, &Arc<Mutex<Player>>)> {
+        self.players
+            .iter()
+            .filter(|(id, _)| !self.blind.contains(id))
+    }
+
+    // Board updates are held back from blacked out players.
+    async fn broadcast_sighted(&self, packet: Packet<'_>) {
+        let message = packet.message();
+        join_all(self.sighted_players().map(|(_, p)| {
+            let message = message.clone();
+            async move {
+                p.lock().await.send(message).await;
+            }
+        }))
+        .await;
+    }
+
+    // Everything a player missed during a blackout, its client dropped the board when it began.
+    async fn resync(&mut self, player_id: PlayerId) {
+        let Some(player) = self.players.get(&player_id).map(Arc::clone) else {
+            return;
+        };
+        let players = join_all(
+            self.players
+                .iter()
+                .map(|(&id, p)| async move { (id, p.lock().await) }),
+        )
+        .await;
+        let snakes_message = Packet::Snakes(
+            players
+                .iter()
+                .map(|(id, p)| (*id, &**p, self.fog.is_none() || *id == player_id))
+                .collect(),
+        )
+        .message();
+        drop(players);
+        let mut player = player.lock().await;
+        player.send(snakes_message).await;
+        if self.fog.is_none() {
+            let perks = self
+                .perks
+                .iter()
+                .filter(|(_, perk)| self.sees(player_id, perk))
+                .map(|(coord, perk)| (*coord, perk.clone()))
+                .collect::<Vec<_>>();
+            player.send(Packet::Perks(perks).message()).await;
+            return;
+        }
+        drop(player);
+        // Everything in sight is sent again.
+        self.sights.remove(&player_id);
+        self.update_sights(&HashSet::new()).await;
     }
 
     async fn broadcast_message(&self, packet: Packet<'_>) {
@@ -380,3 +1799,13 @@ impl Inner {
         .await;
     }
 }
+
+// Cells of the square of the given side, starting with its top left corner.
+fn square(corner: Coord, side: usize) -> impl Iterator<Item = Coord> {
+    (0..side).flat_map(move |dy| {
+        (0..side).map(move |dx| Coord {
+            x: corner.x + dx,
+            y: corner.y + dy,
+        })
+    })
+}
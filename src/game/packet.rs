@@ -1,47 +1,73 @@
-use axum::extract::ws::Message;
-use enum_index::EnumIndex;
-use enum_index_derive::EnumIndex;
-use tokio::sync::MutexGuard;
-
 use crate::{
     game::{
+        bounty::Bounty,
         coordinate::Coord,
+        events::EventKind,
+        flag::{Flag, BASE_RADIUS},
+        hill::Hill,
         perk::Perk,
-        player::{BodyId, Color, Player, PlayerId},
+        player::{BodyId, Color, Effects, Player, PlayerId, Team},
+        round::Round,
         size::Size,
+        topology::Topology,
     },
     misc::PacketSerialize,
     packet,
 };
+use axum::extract::ws::Message;
+use enum_index::EnumIndex;
+use enum_index_derive::EnumIndex;
 
 #[derive(EnumIndex)]
 pub enum Packet<'a> {
-    Info(Size, &'a str, PlayerId),
-    Snakes(Vec<(PlayerId, MutexGuard<'a, Player>)>),
+    Info(Size, &'a str, PlayerId, Topology),
+    // Bodies are left out of the players hidden by the fog of war.
+    Snakes(Vec<(PlayerId, &'a Player, bool)>),
     Perks(Vec<(Coord, Perk)>),
     PlayerJoined(PlayerId, BodyId, Coord, Color),
     PlayerLeft(PlayerId),
     ColorChange(PlayerId, Color),
     SnakeChanges(Vec<SnakeChange>),
+    MovePerks(Vec<(Coord, Coord)>),
+    RemovePerks(Vec<Coord>),
+    Effects(Vec<(PlayerId, Effects)>),
+    Inventory(Vec<Perk>),
+    Portals(Vec<(Coord, Coord)>),
+    Teams(bool, Vec<(PlayerId, Team)>),
+    TeamScores(Vec<u16>),
+    Flags(&'a [Flag]),
+    Hill(&'a Hill),
+    Sight(Vec<Coord>, Vec<(Coord, PlayerId)>),
+    Round(&'a Round),
+    // Winner of the round, nobody when the last snakes died together.
+    RoundOver(Option<PlayerId>),
+    // Host allowed to set the levels, if any, and the level of each player.
+    Handicaps(Option<PlayerId>, Vec<(PlayerId, u8)>),
+    Bounty(&'a Bounty),
+    // World event and the seconds it lasts, zero once it is over.
+    Event(EventKind, u16),
+    Walls(Vec<Coord>),
 }
 
 impl<'a> Packet<'a> {
     pub fn message(self) -> Message {
         let mut payload = packet![cap 256; self.enum_index() as u8];
         match self {
-            Packet::Info(size, name, self_id) => {
+            Packet::Info(size, name, self_id, topology) => {
                 packet![
                     payload;
                     size,
-                    name.as_bytes().len() as u8,
+                    name.len() as u8,
                     name.as_bytes(),
-                    self_id
+                    self_id,
+                    topology
                 ]
             }
             Packet::Snakes(players) => {
-                for (id, player) in players {
-                    packet![payload; id, player.color, player.bodies_len() as u8];
-                    for body in player.bodies_iter() {
+                for (id, player, visible) in players {
+                    let bodies = if visible { player.bodies_len() } else { 0 };
+                    packet![payload; id, player.color, player.selected(), bodies as u8];
+                    for body in player.bodies_iter().take(bodies) {
                         packet![payload; body.id, body.cells.len() as u16];
                         for cell in &body.cells {
                             packet![payload; cell.coord];
@@ -82,19 +108,102 @@ impl<'a> Packet<'a> {
                         SnakeChange::Reverse(player_id) => {
                             packet![payload; player_id]
                         }
+                        SnakeChange::Select(player_id, body_id) => {
+                            packet![payload; player_id, body_id]
+                        }
+                        SnakeChange::Swap(player_id, body_id, other_player_id, other_body_id) => {
+                            packet![payload; player_id, body_id, other_player_id, other_body_id]
+                        }
                     }
                 }
             }
+            Packet::MovePerks(pairs) | Packet::Portals(pairs) => {
+                for (from, to) in pairs {
+                    packet![payload; from, to];
+                }
+            }
+            Packet::RemovePerks(coords) | Packet::Walls(coords) => {
+                for coord in coords {
+                    packet![payload; coord];
+                }
+            }
+            Packet::Effects(effects) => {
+                for (player_id, effects) in effects {
+                    packet![payload; player_id, effects];
+                }
+            }
+            Packet::Inventory(perks) => {
+                for perk in perks {
+                    packet![payload; perk];
+                }
+            }
+            Packet::Teams(shared_ownership, members) => {
+                packet![payload; shared_ownership as u8];
+                for (id, team) in members {
+                    packet![payload; id, team];
+                }
+            }
+            Packet::Handicaps(host, levels) => {
+                packet![payload; host.is_some() as u8, host.unwrap_or_default()];
+                for (id, level) in levels {
+                    packet![payload; id, level];
+                }
+            }
+            Packet::TeamScores(scores) => {
+                for score in scores {
+                    packet![payload; score];
+                }
+            }
+            Packet::Hill(hill) => packet![payload; hill],
+            Packet::Round(round) => packet![payload; round],
+            Packet::Bounty(bounty) => packet![payload; bounty],
+            Packet::Event(kind, duration) => packet![payload; kind as u8, duration],
+            Packet::RoundOver(winner) => {
+                if let Some(winner) = winner {
+                    packet![payload; winner];
+                }
+            }
+            Packet::Flags(flags) => {
+                packet![payload; BASE_RADIUS as u8];
+                for flag in flags {
+                    packet![payload; flag];
+                }
+            }
+            Packet::Sight(hidden, shown) => {
+                packet![payload; hidden.len() as u16];
+                for coord in hidden {
+                    packet![payload; coord];
+                }
+                for (coord, player_id) in shown {
+                    packet![payload; coord, player_id];
+                }
+            }
         };
         Message::Binary(payload)
     }
 }
 
-#[derive(EnumIndex, Debug)]
+#[derive(EnumIndex, Clone, Debug)]
 pub enum SnakeChange {
     RemoveTail(PlayerId, BodyId),
     AddCell(PlayerId, BodyId, Coord),
     AddBody(PlayerId, BodyId, Coord),
     RemoveBody(PlayerId, BodyId),
     Reverse(PlayerId),
+    Swap(PlayerId, BodyId, PlayerId, BodyId),
+    Select(PlayerId, BodyId),
+}
+
+impl SnakeChange {
+    pub fn player_id(&self) -> PlayerId {
+        match *self {
+            SnakeChange::RemoveTail(player_id, _)
+            | SnakeChange::AddCell(player_id, _, _)
+            | SnakeChange::AddBody(player_id, _, _)
+            | SnakeChange::RemoveBody(player_id, _)
+            | SnakeChange::Reverse(player_id)
+            | SnakeChange::Swap(player_id, _, _, _)
+            | SnakeChange::Select(player_id, _) => player_id,
+        }
+    }
 }
@@ -14,11 +14,13 @@ use crate::{
         config::Config,
         coordinate::Coord,
         packet::SnakeChange,
-        player::{BodyId, Player, PlayerId},
+        player::{BodyCell, BodyId, Player, PlayerId},
     },
     misc::PacketSerialize,
 };
 
+const CRITTER_STRENGTH_MULTIPLIER: u16 = 3;
+
 #[derive(Clone, Debug)]
 pub struct Perk {
     group_id: u16,
@@ -37,18 +39,26 @@ impl Perk {
         Self::new(PerkKind::Mine(owner))
     }
 
+    // Food that doesn't respawn once eaten.
+    pub fn new_food(strength: u16) -> Self {
+        Self::new(PerkKind::Food(strength, false))
+    }
+
+    // `allies` are the players sharing ownership of reserved foods and mines with the consumer,
+    // including itself.
     pub async fn consume(
         &self,
         player_id: PlayerId,
         body_id: BodyId,
         player: &mut Player,
         perks: &HashMap<Coord, Perk>,
+        allies: &[PlayerId],
     ) -> PerkConsumption {
         let mut consumption = PerkConsumption::default();
         match self.kind {
             PerkKind::Food(strength, _) => player.grow(strength),
             PerkKind::ReservedFood { strength, owner } => {
-                if owner == player_id {
+                if allies.contains(&owner) {
                     player.grow(strength);
                 }
             }
@@ -85,13 +95,48 @@ impl Perk {
                 player.increase_mines_count(count as u16);
             }
             PerkKind::Mine(owner) => {
-                if owner != player_id {
+                if !allies.contains(&owner) {
                     consumption.should_die = true;
                 }
             }
             PerkKind::MultiSnake => {
                 consumption.should_multiply = Some(2);
             }
+            PerkKind::Shrink(percentage) => {
+                consumption.removed_tails = player.shrink(percentage);
+            }
+            PerkKind::Freeze(duration) => {
+                player.freeze(duration);
+            }
+            PerkKind::Invert(duration) => {
+                player.invert_controls(duration);
+            }
+            PerkKind::Magnet(duration) => {
+                player.attract(duration);
+            }
+            PerkKind::Bomb(radius) => {
+                consumption.blast = player
+                    .get_body(body_id)
+                    .and_then(|b| b.cells.front())
+                    .map(|c| (c.coord, radius));
+            }
+            PerkKind::Swapper => {
+                consumption.should_swap = true;
+            }
+            PerkKind::Blackout(duration) => {
+                consumption.effect_on_others = Some(Effect::Blackout(duration));
+            }
+            PerkKind::SlowOthers(duration) => {
+                consumption.effect_on_others = Some(Effect::Slow(duration));
+            }
+            PerkKind::ReverseOthers => {
+                consumption.effect_on_others = Some(Effect::Reverse);
+            }
+            PerkKind::Critter(strength) => {
+                player.grow(strength);
+                consumption.additional_perks.push(self.clone());
+            }
+            PerkKind::BigFood { strength, .. } => player.grow(strength),
         }
         consumption
     }
@@ -99,6 +144,47 @@ impl Perk {
     pub fn makes_spawn_food(&self) -> bool {
         matches!(self.kind, PerkKind::Food(_, true))
     }
+
+    // Perks that can be kept in the inventory and activated later.
+    pub fn is_storable(&self) -> bool {
+        matches!(
+            self.kind,
+            PerkKind::SpeedBoost(_)
+                | PerkKind::MinesTrail(_)
+                | PerkKind::Reverser
+                | PerkKind::MultiSnake
+        )
+    }
+
+    pub fn is_critter(&self) -> bool {
+        matches!(self.kind, PerkKind::Critter(_))
+    }
+
+    pub fn is_mine(&self) -> bool {
+        matches!(self.kind, PerkKind::Mine(_))
+    }
+
+    pub fn mine_owner(&self) -> Option<PlayerId> {
+        match self.kind {
+            PerkKind::Mine(owner) => Some(owner),
+            _ => None,
+        }
+    }
+
+    // Length of the side of the square covered by the perk.
+    pub fn side(&self) -> usize {
+        match self.kind {
+            PerkKind::BigFood { side, .. } => side as usize,
+            _ => 1,
+        }
+    }
+
+    pub fn is_food(&self) -> bool {
+        matches!(
+            self.kind,
+            PerkKind::Food(..) | PerkKind::ReservedFood { .. }
+        )
+    }
 }
 
 impl PacketSerialize for Perk {
@@ -107,6 +193,7 @@ impl PacketSerialize for Perk {
         match self.kind {
             PerkKind::ReservedFood { owner, .. } => out.write_u16::<BE>(owner).unwrap(),
             PerkKind::Mine(owner) => out.write_u16::<BE>(owner).unwrap(),
+            PerkKind::BigFood { side, .. } => out.push(side),
             _ => (),
         }
     }
@@ -123,6 +210,25 @@ enum PerkKind {
     MinesTrail(u8),
     Mine(PlayerId),
     MultiSnake,
+    Shrink(u8),
+    Freeze(u16),
+    Invert(u16),
+    Magnet(u16),
+    Bomb(u8),
+    Swapper,
+    Blackout(u16),
+    SlowOthers(u16),
+    ReverseOthers,
+    Critter(u16),
+    BigFood { side: u8, strength: u16 },
+}
+
+// Effect applied by a perk to players other than its consumer.
+#[derive(Copy, Clone, Debug)]
+pub enum Effect {
+    Blackout(u16),
+    Slow(u16),
+    Reverse,
 }
 
 #[derive(Default, Debug)]
@@ -131,6 +237,10 @@ pub struct PerkConsumption {
     pub additional_perks: Vec<Perk>,
     pub should_die: bool,
     pub should_multiply: Option<u8>,
+    pub removed_tails: Vec<(BodyId, BodyCell)>,
+    pub blast: Option<(Coord, u8)>,
+    pub should_swap: bool,
+    pub effect_on_others: Option<Effect>,
 }
 
 pub struct Generator {
@@ -142,6 +252,14 @@ pub struct Generator {
     speed_boost: Option<u16>,
     food_frenzy: Option<u8>,
     mines_trail: Option<u8>,
+    shrink: Option<u8>,
+    freeze: Option<u16>,
+    invert: Option<u16>,
+    magnet: Option<u16>,
+    bomb: Option<u8>,
+    blackout: Option<u16>,
+    slow_others: Option<u16>,
+    big_food: Option<u8>,
     enabled_perks_fn: Vec<fn(&Generator) -> Vec<Perk>>,
 }
 
@@ -168,6 +286,26 @@ impl Generator {
             config
                 .multi_snake
                 .then_some(Generator::multi_snake as PerkGeneratorFn),
+            config.shrink.map(|_| Generator::shrink as PerkGeneratorFn),
+            config.freeze.map(|_| Generator::freeze as PerkGeneratorFn),
+            config.invert.map(|_| Generator::invert as PerkGeneratorFn),
+            config.magnet.map(|_| Generator::magnet as PerkGeneratorFn),
+            config.bomb.map(|_| Generator::bomb as PerkGeneratorFn),
+            config
+                .swapper
+                .then_some(Generator::swapper as PerkGeneratorFn),
+            config
+                .blackout
+                .map(|_| Generator::blackout as PerkGeneratorFn),
+            config
+                .slow_others
+                .map(|_| Generator::slow_others as PerkGeneratorFn),
+            config
+                .reverse_others
+                .then_some(Generator::reverse_others as PerkGeneratorFn),
+            config
+                .big_food
+                .map(|_| Generator::big_food as PerkGeneratorFn),
         ]
         .into_iter()
         .flatten()
@@ -183,6 +321,14 @@ impl Generator {
             speed_boost: config.speed_boost,
             food_frenzy: config.food_frenzy,
             mines_trail: config.mines_trail,
+            shrink: config.shrink,
+            freeze: config.freeze,
+            invert: config.invert,
+            magnet: config.magnet,
+            bomb: config.bomb,
+            blackout: config.blackout,
+            slow_others: config.slow_others,
+            big_food: config.big_food,
             enabled_perks_fn,
         }
     }
@@ -203,7 +349,8 @@ impl Generator {
             }
         }
 
-        if !self.enabled_perks_fn.is_empty() && self.food_consumed % self.perk_spacing == 0 {
+        if !self.enabled_perks_fn.is_empty() && self.food_consumed.is_multiple_of(self.perk_spacing)
+        {
             let next_perk_fn_idx =
                 (self.food_consumed / self.perk_spacing) as usize % self.enabled_perks_fn.len();
             perks.extend(self.enabled_perks_fn[next_perk_fn_idx](self));
@@ -216,6 +363,16 @@ impl Generator {
         Perk::new(PerkKind::Food(self.food_strength, true))
     }
 
+    pub fn food(&self) -> Perk {
+        Perk::new_food(self.food_strength)
+    }
+
+    pub fn critter(&self) -> Perk {
+        Perk::new(PerkKind::Critter(
+            self.food_strength * CRITTER_STRENGTH_MULTIPLIER,
+        ))
+    }
+
     fn reverser(&self) -> Vec<Perk> {
         vec![Perk::new(PerkKind::Reverser)]
     }
@@ -242,6 +399,51 @@ impl Generator {
     fn multi_snake(&self) -> Vec<Perk> {
         vec![Perk::new(PerkKind::MultiSnake)]
     }
+
+    fn shrink(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Shrink(self.shrink.unwrap()))]
+    }
+
+    fn freeze(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Freeze(self.freeze.unwrap()))]
+    }
+
+    fn invert(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Invert(self.invert.unwrap()))]
+    }
+
+    fn magnet(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Magnet(self.magnet.unwrap()))]
+    }
+
+    fn bomb(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Bomb(self.bomb.unwrap()))]
+    }
+
+    fn swapper(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Swapper)]
+    }
+
+    fn blackout(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::Blackout(self.blackout.unwrap()))]
+    }
+
+    fn slow_others(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::SlowOthers(self.slow_others.unwrap()))]
+    }
+
+    fn reverse_others(&self) -> Vec<Perk> {
+        vec![Perk::new(PerkKind::ReverseOthers)]
+    }
+
+    // As nourishing as a food per covered cell.
+    fn big_food(&self) -> Vec<Perk> {
+        let side = self.big_food.unwrap();
+        vec![Perk::new(PerkKind::BigFood {
+            side,
+            strength: self.food_strength * (side as u16).pow(2),
+        })]
+    }
 }
 
 impl Debug for Generator {
@@ -1,19 +1,33 @@
-use std::{collections::VecDeque, convert::TryFrom};
+use std::{collections::VecDeque, convert::TryFrom, sync::Arc};
 
 use axum::extract::ws::{Message, WebSocket};
 use futures::{stream::SplitSink, SinkExt};
 use rand::{random, thread_rng, Rng};
 use tokio::sync::Mutex;
 
-use crate::game::{coordinate::Coord, direction::Dir, perk::Perk, size::Size, speed::Speed};
+use crate::{
+    game::{
+        coordinate::Coord,
+        direction::{Dir, Movement},
+        handicap::Handicap,
+        map::Map,
+        perk::{Effect, Perk},
+        speed::Speed,
+    },
+    misc::PacketSerialize,
+};
 
 const START_SIZE: u16 = 9;
 const TRAIL_PERK_SPACING: u16 = 10;
 const COLOR_GAP: u16 = 60;
+const INVENTORY_SIZE: usize = 3;
+const BOOST_SHED_SPACING: u16 = 4;
+const BOOST_MIN_LENGTH: usize = 3;
 
 pub(super) type PlayerId = u16;
 pub(super) type BodyId = u16;
 pub(super) type Color = u16;
+pub(super) type Team = u8;
 
 #[derive(Debug)]
 pub struct Player {
@@ -21,8 +35,26 @@ pub struct Player {
     pub color: Color,
     bodies: Vec<Body>,
     direction: Mutex<Direction>,
+    // Each body has its own direction, the selected one receives the inputs.
+    independent: bool,
+    selected: BodyId,
+    movement: Movement,
+    map: Arc<Map>,
     speed: u16,
+    boosting: bool,
+    until_shed: u16,
+    frozen: u16,
+    inverted: u16,
+    magnet: u16,
+    // Sudden death speeds everyone up.
+    rushing: bool,
+    afflictions: Afflictions,
+    sent_effects: Effects,
     perk_trail: PerkTrail,
+    inventory: Vec<Perk>,
+    pub handicap: Handicap,
+    // Enemy flag carried by one of the bodies.
+    flag: Option<(BodyId, Team)>,
     sink: SplitSink<WebSocket, Message>,
 }
 
@@ -32,21 +64,53 @@ struct Direction {
     queue: VecDeque<Dir>,
 }
 
+impl Direction {
+    fn push(&mut self, new: Dir) {
+        let last = self.queue.back().copied().or(self.current);
+        if let Some(dir) = last {
+            if !dir.conflict(&new) {
+                self.queue.push_back(new);
+            }
+        } else {
+            self.queue.push_back(new);
+        }
+    }
+
+    fn next(&mut self) -> Option<Dir> {
+        let next = self.queue.pop_front().or(self.current)?;
+        self.current = Some(next);
+        Some(next)
+    }
+
+    fn set(&mut self, current: Option<Dir>) {
+        self.current = current;
+        self.queue.clear();
+    }
+}
+
 #[derive(Debug)]
 pub struct Body {
     pub id: BodyId,
     pub cells: VecDeque<BodyCell>,
     growth: u16,
+    // Only used by independent bodies.
+    direction: Direction,
 }
 
 impl Body {
-    fn new(head: Coord) -> Self {
+    fn new(head: Coord, growth: u16) -> Self {
         Self {
             id: random(),
             cells: VecDeque::from([BodyCell::without_perk(head)]),
-            growth: START_SIZE,
+            growth,
+            direction: Direction::default(),
         }
     }
+
+    fn head_direction(&self, map: &Map, movement: Movement) -> Option<Dir> {
+        let (head, body) = (self.cells.front()?, self.cells.get(1)?);
+        Some(map.direction(head.coord, body.coord, movement))
+    }
 }
 
 #[derive(Debug)]
@@ -95,9 +159,69 @@ impl PerkTrail {
     }
 }
 
+// Timed effects inflicted by other players.
+#[derive(Default, Copy, Clone, Debug)]
+struct Afflictions {
+    blackout: u16,
+    slowed: u16,
+}
+
+impl Afflictions {
+    fn tick(&mut self) {
+        self.blackout = self.blackout.saturating_sub(1);
+        self.slowed = self.slowed.saturating_sub(1);
+    }
+}
+
+// Snapshot of the remaining ticks (or mines) of every timed effect of a player.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Effects {
+    speed: u16,
+    frozen: u16,
+    inverted: u16,
+    magnet: u16,
+    mines: u16,
+    blackout: u16,
+    slowed: u16,
+}
+
+impl Effects {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Remaining mines are kept secret when mines are hidden.
+    pub fn without_mines(self) -> Self {
+        Self { mines: 0, ..self }
+    }
+}
+
+impl PacketSerialize for Effects {
+    fn push(&self, out: &mut Vec<u8>) {
+        for value in [
+            self.speed,
+            self.frozen,
+            self.inverted,
+            self.magnet,
+            self.mines,
+            self.blackout,
+            self.slowed,
+        ] {
+            value.push(out);
+        }
+    }
+}
+
 impl Player {
-    pub fn new(id: PlayerId, head: Coord, tx: SplitSink<WebSocket, Message>) -> (Self, BodyId) {
-        let body = Body::new(head);
+    pub fn new(
+        id: PlayerId,
+        head: Coord,
+        independent: bool,
+        movement: Movement,
+        map: Arc<Map>,
+        tx: SplitSink<WebSocket, Message>,
+    ) -> (Self, BodyId) {
+        let body = Body::new(head, START_SIZE);
         let body_id = body.id;
         (
             Self {
@@ -105,8 +229,23 @@ impl Player {
                 color: thread_rng().gen_range(0..360),
                 bodies: vec![body],
                 direction: Mutex::new(Direction::default()),
+                independent,
+                selected: body_id,
+                movement,
+                map,
                 speed: 0,
+                boosting: false,
+                until_shed: BOOST_SHED_SPACING,
+                frozen: 0,
+                inverted: 0,
+                magnet: 0,
+                rushing: false,
+                afflictions: Afflictions::default(),
+                sent_effects: Effects::default(),
                 perk_trail: PerkTrail::empty(),
+                inventory: Vec::with_capacity(INVENTORY_SIZE),
+                handicap: Handicap::default(),
+                flag: None,
                 sink: tx,
             },
             body_id,
@@ -118,8 +257,11 @@ impl Player {
     }
 
     pub fn add_body(&mut self, head: Coord) -> BodyId {
-        let body = Body::new(head);
+        let body = Body::new(head, START_SIZE + self.handicap.extra_start_size());
         let id = body.id;
+        if self.bodies.is_empty() {
+            self.selected = id;
+        }
         self.bodies.push(body);
         id
     }
@@ -128,16 +270,29 @@ impl Player {
         let removed = self
             .bodies
             .remove(self.bodies.iter().position(|body| body.id == id)?);
+        if let Some(first) = self.bodies.first().filter(|_| self.selected == id) {
+            self.selected = first.id;
+        }
         if self.bodies.is_empty() {
-            let mut direction = self.direction.lock().await;
-            direction.current = None;
-            direction.queue.clear();
+            self.direction.lock().await.set(None);
             self.speed = 0;
+            self.frozen = 0;
+            self.inverted = 0;
+            self.magnet = 0;
             self.perk_trail = PerkTrail::empty();
         }
         Some(removed.cells)
     }
 
+    pub fn truncate_body(&mut self, id: BodyId, len: usize) -> VecDeque<BodyCell> {
+        self.bodies
+            .iter_mut()
+            .find(|b| b.id == id)
+            .filter(|b| len < b.cells.len())
+            .map(|b| b.cells.split_off(len))
+            .unwrap_or_default()
+    }
+
     pub fn get_body(&self, id: BodyId) -> Option<&Body> {
         self.bodies.iter().find(|b| b.id == id)
     }
@@ -150,39 +305,107 @@ impl Player {
         self.bodies.iter()
     }
 
-    pub async fn process_move_event(&self, data: &[u8]) {
-        let Some(&id) = data.get(0) else {
+    pub fn length(&self) -> usize {
+        self.bodies.iter().map(|b| b.cells.len()).sum()
+    }
+
+    pub fn is_independent(&self) -> bool {
+        self.independent
+    }
+
+    pub fn selected(&self) -> BodyId {
+        self.selected
+    }
+
+    // Select the next body to receive inputs, or the one at the given index.
+    pub fn select(&mut self, index: Option<usize>) -> Option<BodyId> {
+        if !self.independent {
+            return None;
+        }
+        let current = self.bodies.iter().position(|b| b.id == self.selected)?;
+        let next = index.unwrap_or(current + 1) % self.bodies.len();
+        self.selected = self.bodies[next].id;
+        Some(self.selected)
+    }
+
+    async fn body_direction(&self, body_id: BodyId) -> Option<Dir> {
+        if self.independent {
+            self.get_body(body_id)?.direction.current
+        } else {
+            self.direction.lock().await.current
+        }
+    }
+
+    async fn set_body_direction(&mut self, body_id: BodyId, current: Option<Dir>) {
+        if !self.independent {
+            self.direction.lock().await.set(current);
+        } else if let Some(body) = self.bodies.iter_mut().find(|b| b.id == body_id) {
+            body.direction.set(current);
+        }
+    }
+
+    pub async fn process_move_event(&mut self, data: &[u8]) {
+        let Some(&id) = data.first() else {
             return;
         };
         let Ok(new) = Dir::try_from(id) else {
             return;
         };
-
-        let mut direction = self.direction.lock().await;
-        let last = direction.queue.back().copied().or(direction.current);
-        if let Some(dir) = last {
-            if !dir.conflict(&new) {
-                direction.queue.push_back(new);
-            }
+        if !self.map.topology.allows(new, self.movement) {
+            return;
+        }
+        let new = if self.inverted > 0 {
+            new.opposite()
         } else {
-            direction.queue.push_back(new);
+            new
+        };
+
+        if !self.independent {
+            self.direction.lock().await.push(new);
+        } else if let Some(body) = self.bodies.iter_mut().find(|b| b.id == self.selected) {
+            body.direction.push(new);
         }
     }
 
-    pub async fn walk(
-        &mut self,
-        grid_size: Size,
-    ) -> Option<Vec<(BodyId, Option<BodyCell>, Coord)>> {
-        let mut direction = self.direction.lock().await;
-        let new_direction = direction.queue.pop_front().or(direction.current)?;
-        direction.current = Some(new_direction);
-        drop(direction);
+    pub async fn walk(&mut self) -> Option<Vec<(BodyId, Option<BodyCell>, Coord)>> {
+        // Slowed snakes skip every other move.
+        if self.afflictions.slowed % 2 == 1 {
+            return None;
+        }
+        if self.frozen > 0 {
+            return None;
+        }
+
+        let directions = if self.independent {
+            self.bodies
+                .iter_mut()
+                .map(|b| b.direction.next())
+                .collect::<Vec<_>>()
+        } else {
+            vec![Some(self.direction.lock().await.next()?); self.bodies.len()]
+        };
+        if directions.iter().all(Option::is_none) {
+            return None;
+        }
 
         let mut changes = Vec::with_capacity(self.bodies.len());
+        let mut mirrored = None;
         let mine = self.perk_trail.next(self.id);
-        for body in &mut self.bodies {
-            let current_head_coord = body.cells.get(0).unwrap().coord;
-            let new_head_coord = current_head_coord + (new_direction, grid_size);
+        for (body, new_direction) in self.bodies.iter_mut().zip(directions) {
+            let Some(new_direction) = new_direction else {
+                continue;
+            };
+            let current_head_coord = body.cells.front().unwrap().coord;
+            let (new_head_coord, next_direction) = self.map.step(current_head_coord, new_direction);
+            // Crossing a mirrored edge changes the direction, of the whole snake when the
+            // bodies are steered together.
+            if next_direction != new_direction {
+                if self.independent {
+                    body.direction.current = Some(next_direction);
+                } else {
+                    mirrored = Some(next_direction);
+                }
+            }
 
             body.cells.push_front(BodyCell {
                 coord: new_head_coord,
@@ -196,29 +419,162 @@ impl Player {
             };
             changes.push((body.id, tail, new_head_coord));
         }
+        if mirrored.is_some() {
+            self.direction.lock().await.current = mirrored;
+        }
         self.speed = self.speed.saturating_sub(1);
+        self.magnet = self.magnet.saturating_sub(1);
 
         Some(changes)
     }
 
+    // Count down the effects lasting a number of game ticks.
+    pub fn tick(&mut self) {
+        self.frozen = self.frozen.saturating_sub(1);
+        self.inverted = self.inverted.saturating_sub(1);
+        self.afflictions.tick();
+    }
+
     pub fn grow(&mut self, grow: u16) {
+        let grow = self.handicap.food(grow);
         for body in &mut self.bodies {
             body.growth += grow;
         }
     }
 
+    pub fn shrink(&mut self, percentage: u8) -> Vec<(BodyId, BodyCell)> {
+        let mut removed = Vec::new();
+        for body in &mut self.bodies {
+            // Always keep the head.
+            let count = (body.cells.len() * percentage as usize / 100).min(body.cells.len() - 1);
+            for _ in 0..count {
+                removed.push((body.id, body.cells.pop_back().unwrap()));
+            }
+        }
+        removed
+    }
+
+    pub fn blacked_out(&self) -> bool {
+        self.afflictions.blackout > 0
+    }
+
     pub fn speed(&self) -> Speed {
-        if self.speed > 0 {
+        if self.speed > 0 || self.rushing || self.boosting_voluntarily() {
             Speed::Fast
         } else {
             Speed::Normal
         }
     }
 
+    pub fn set_boosting(&mut self, boosting: bool) {
+        self.boosting = boosting;
+    }
+
+    // Boosting by holding the key, not by a speed boost perk, while long enough to pay for it.
+    fn boosting_voluntarily(&self) -> bool {
+        self.boosting
+            && self.speed == 0
+            && self.bodies.iter().any(|b| b.cells.len() > BOOST_MIN_LENGTH)
+    }
+
+    // Cells lost by voluntary boosting since the last walk.
+    pub fn shed(&mut self) -> Vec<(BodyId, BodyCell)> {
+        if !self.boosting_voluntarily() {
+            return Vec::new();
+        }
+        self.until_shed -= 1;
+        if self.until_shed > 0 {
+            return Vec::new();
+        }
+        self.until_shed = BOOST_SHED_SPACING;
+        self.bodies
+            .iter_mut()
+            .filter(|b| b.cells.len() > BOOST_MIN_LENGTH)
+            .map(|b| (b.id, b.cells.pop_back().unwrap()))
+            .collect()
+    }
+
+    // Portals may change during the game.
+    pub fn set_map(&mut self, map: Arc<Map>) {
+        self.map = map;
+    }
+
+    pub fn rush(&mut self, rushing: bool) {
+        self.rushing = rushing;
+    }
+
     pub fn increase_speed(&mut self, duration: u16) {
         self.speed += duration;
     }
 
+    pub fn freeze(&mut self, duration: u16) {
+        self.frozen += duration;
+    }
+
+    pub fn invert_controls(&mut self, duration: u16) {
+        self.inverted += duration;
+    }
+
+    pub fn attract(&mut self, duration: u16) {
+        self.magnet += duration;
+    }
+
+    pub fn heads(&self) -> Vec<Coord> {
+        self.bodies
+            .iter()
+            .filter_map(|b| b.cells.front().map(|c| c.coord))
+            .collect()
+    }
+
+    pub fn heads_if_magnetic(&self) -> Vec<Coord> {
+        if self.magnet == 0 {
+            return Vec::new();
+        }
+        self.heads()
+    }
+
+    pub fn store(&mut self, perk: &Perk) -> bool {
+        if self.inventory.len() >= INVENTORY_SIZE {
+            return false;
+        }
+        self.inventory.push(perk.clone());
+        true
+    }
+
+    pub fn take_stored(&mut self, slot: usize) -> Option<Perk> {
+        (slot < self.inventory.len()).then(|| self.inventory.remove(slot))
+    }
+
+    pub fn inventory(&self) -> Vec<Perk> {
+        self.inventory.clone()
+    }
+
+    pub async fn suffer(&mut self, effect: Effect) {
+        match effect {
+            Effect::Blackout(duration) => self.afflictions.blackout += duration,
+            Effect::Slow(duration) => self.afflictions.slowed += duration,
+            Effect::Reverse => self.reverse().await,
+        }
+    }
+
+    pub fn effects(&self) -> Effects {
+        Effects {
+            speed: self.speed,
+            frozen: self.frozen,
+            inverted: self.inverted,
+            magnet: self.magnet,
+            mines: self.perk_trail.remaining,
+            blackout: self.afflictions.blackout,
+            slowed: self.afflictions.slowed,
+        }
+    }
+
+    // Effects that changed since the last call, if any.
+    pub fn take_changed_effects(&mut self) -> Option<Effects> {
+        let effects = self.effects();
+        (effects != std::mem::replace(&mut self.sent_effects, effects)).then_some(effects)
+    }
+
     pub fn increase_mines_count(&mut self, count: u16) {
         self.perk_trail.add_mines(count);
     }
@@ -231,19 +587,19 @@ impl Player {
 
         for body in &mut self.bodies {
             body.cells.make_contiguous().reverse();
+            if self.independent {
+                body.direction
+                    .set(body.head_direction(&self.map, self.movement));
+            }
         }
-        let mut direction = self.direction.lock().await;
-        if let (Some(head), Some(body)) = (self.bodies[0].cells.get(0), self.bodies[0].cells.get(1))
-        {
-            direction.current = Some(Dir::from((head.coord, body.coord)));
-        } else {
-            direction.current = None;
+        if !self.independent {
+            let current = self.bodies[0].head_direction(&self.map, self.movement);
+            self.direction.lock().await.set(current);
         }
-        direction.queue.clear();
     }
 
     pub async fn teleport(&mut self, body_id: BodyId, coord: Coord) -> bool {
-        if self.direction.lock().await.current.is_none() {
+        if self.body_direction(body_id).await.is_none() {
             return false;
         }
         let Some(body) = self.bodies.iter_mut().find(|b| b.id == body_id) else {
@@ -253,6 +609,60 @@ impl Player {
         true
     }
 
+    // Exchange the cells of one of our bodies with one of another player's bodies,
+    // along with the current directions.
+    pub async fn swap_body(
+        &mut self,
+        body_id: BodyId,
+        other: &mut Player,
+        other_body_id: BodyId,
+    ) -> bool {
+        let direction = self.body_direction(body_id).await;
+        let other_direction = other.body_direction(other_body_id).await;
+        let (Some(body), Some(other_body)) = (
+            self.bodies.iter_mut().find(|b| b.id == body_id),
+            other.bodies.iter_mut().find(|b| b.id == other_body_id),
+        ) else {
+            return false;
+        };
+        std::mem::swap(&mut body.cells, &mut other_body.cells);
+
+        // A shared direction steers the other bodies too, so it is left alone.
+        if self.steers_alone() {
+            self.set_body_direction(body_id, other_direction).await;
+        }
+        if other.steers_alone() {
+            other.set_body_direction(other_body_id, direction).await;
+        }
+        true
+    }
+
+    fn steers_alone(&self) -> bool {
+        self.independent || self.bodies.len() == 1
+    }
+
+    pub fn carry_flag(&mut self, body_id: BodyId, team: Team) -> bool {
+        if self.flag.is_some() {
+            return false;
+        }
+        self.flag = Some((body_id, team));
+        true
+    }
+
+    pub fn carried_flag(&self) -> Option<(BodyId, Team)> {
+        self.flag
+    }
+
+    // Release the flag along with the head of the body carrying it.
+    pub fn release_flag(&mut self) -> Option<(Team, Option<Coord>)> {
+        let (body_id, team) = self.flag.take()?;
+        let head = self
+            .get_body(body_id)
+            .and_then(|b| b.cells.front())
+            .map(|c| c.coord);
+        Some((team, head))
+    }
+
     pub fn change_color(&mut self) -> Color {
         self.color =
             (self.color + COLOR_GAP + thread_rng().gen_range(0..360 - COLOR_GAP * 2)) % 360;
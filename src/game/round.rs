@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use crate::misc::PacketSerialize;
+
+// Rounds turn into sudden death after a time limit: nobody respawns, food stops spawning and
+// every snake speeds up until a single one is left.
+#[derive(Debug)]
+pub struct Round {
+    limit: Duration,
+    started: Instant,
+    pub sudden_death: bool,
+}
+
+impl Round {
+    pub fn new(limit: u16) -> Self {
+        Self {
+            limit: Duration::from_secs(limit as u64),
+            started: Instant::now(),
+            sudden_death: false,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        !self.sudden_death && self.started.elapsed() >= self.limit
+    }
+
+    pub fn restart(&mut self) {
+        self.started = Instant::now();
+        self.sudden_death = false;
+    }
+
+    // Seconds left before sudden death.
+    fn remaining(&self) -> u16 {
+        self.limit.saturating_sub(self.started.elapsed()).as_secs() as u16
+    }
+}
+
+impl PacketSerialize for Round {
+    fn push(&self, out: &mut Vec<u8>) {
+        (self.sudden_death as u8).push(out);
+        self.remaining().push(out);
+    }
+}
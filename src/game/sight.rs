@@ -0,0 +1,75 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::game::{cell::Cell, coordinate::Coord, perk::Perk, player::PlayerId};
+
+// What a player has been sent of the others' snakes and of the perks through the fog of war.
+#[derive(Default, Debug)]
+pub struct Sight {
+    cells: HashMap<Coord, PlayerId>,
+    perks: HashSet<Coord>,
+}
+
+#[derive(Default, Debug)]
+pub struct SightUpdate {
+    pub hidden_cells: Vec<Coord>,
+    pub shown_cells: Vec<(Coord, PlayerId)>,
+    pub hidden_perks: Vec<Coord>,
+    pub shown_perks: Vec<(Coord, Perk)>,
+}
+
+impl Sight {
+    // Differences between what was seen and what is now visible. Perks on `touched` cells are
+    // sent again as they may have been replaced, those the viewer may not see are left out.
+    pub fn update(
+        &mut self,
+        viewer: PlayerId,
+        visible: &HashSet<Coord>,
+        grid: &[Vec<Cell>],
+        touched: &HashSet<Coord>,
+        sees: impl Fn(&Perk) -> bool,
+    ) -> SightUpdate {
+        let mut cells = HashMap::new();
+        let mut perks = HashSet::new();
+        let mut update = SightUpdate::default();
+        for &coord in visible {
+            match &grid[coord.y][coord.x] {
+                // Players always follow their own snakes.
+                Cell::Occupied(id) if *id != viewer => {
+                    if self.cells.get(&coord) != Some(id) {
+                        update.shown_cells.push((coord, *id));
+                    }
+                    cells.insert(coord, *id);
+                }
+                // Any visible cell of a multi-cell perk shows it whole.
+                cell @ (Cell::Perk(_) | Cell::PerkPart(_)) => {
+                    let anchor = match cell {
+                        Cell::PerkPart(anchor) => *anchor,
+                        _ => coord,
+                    };
+                    let Cell::Perk(perk) = &grid[anchor.y][anchor.x] else {
+                        continue;
+                    };
+                    if !sees(perk) {
+                        continue;
+                    }
+                    if perks.insert(anchor)
+                        && (!self.perks.contains(&anchor) || touched.contains(&anchor))
+                    {
+                        update.shown_perks.push((anchor, perk.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        update.hidden_cells = self
+            .cells
+            .keys()
+            .filter(|c| !cells.contains_key(c))
+            .copied()
+            .collect();
+        update.hidden_perks = self.perks.difference(&perks).copied().collect();
+        self.cells = cells;
+        self.perks = perks;
+        update
+    }
+}
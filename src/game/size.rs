@@ -8,7 +8,7 @@ pub struct Size {
 
 impl PacketSerialize for Size {
     fn push(&self, out: &mut Vec<u8>) {
-        (self.width as u16).push(out);
-        (self.height as u16).push(out);
+        self.width.push(out);
+        self.height.push(out);
     }
 }
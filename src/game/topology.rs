@@ -0,0 +1,166 @@
+use crate::{
+    game::{
+        coordinate::{Coord, Wrap},
+        direction::{Dir, Movement},
+        size::Size,
+    },
+    misc::PacketSerialize,
+};
+
+// Hexagonal grids use offset coordinates: odd rows are shifted half a cell to the right
+// and the snakes move along the six diagonal and horizontal directions.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Topology {
+    Square(Wrap),
+    Hexagonal,
+}
+
+impl Topology {
+    pub fn allows(&self, dir: Dir, movement: Movement) -> bool {
+        match self {
+            Self::Square(_) => movement.allows(dir),
+            Self::Hexagonal => !matches!(dir, Dir::Up | Dir::Down),
+        }
+    }
+
+    // Next coordinate along with the direction the snake has after the move.
+    pub fn step(&self, coord: Coord, dir: Dir, size: Size) -> (Coord, Dir) {
+        match *self {
+            Self::Square(wrap) => coord + (dir, size, wrap),
+            Self::Hexagonal => {
+                let (dx, dy) = dir.delta();
+                // Going up or down from an even row lands on the same or the previous column,
+                // from an odd row on the same or the next one.
+                let dx = match (dy, coord.y % 2) {
+                    (0, _) => dx,
+                    (_, 0) => dx.min(0),
+                    _ => dx.max(0),
+                };
+                (coord.shift((dx, dy), size), dir)
+            }
+        }
+    }
+
+    pub fn distance(&self, from: Coord, to: Coord, size: Size) -> usize {
+        match self {
+            // Mirrored edges are ignored, the torus distance is close enough.
+            Self::Square(_) => from.distance(to, size),
+            Self::Hexagonal => {
+                // Cube coordinates, trying every copy of the target around the wrapping edges.
+                fn cube(x: isize, y: isize) -> (isize, isize) {
+                    (x - (y - (y & 1)) / 2, y)
+                }
+                let (width, height) = (size.width as isize, size.height as isize);
+                let (q, r) = cube(from.x as isize, from.y as isize);
+                [-width, 0, width]
+                    .into_iter()
+                    .flat_map(|ox| [-height, 0, height].map(|oy| (ox, oy)))
+                    .map(|(ox, oy)| {
+                        let (tq, tr) = cube(to.x as isize + ox, to.y as isize + oy);
+                        let (dq, dr) = (tq - q, tr - r);
+                        (dq.unsigned_abs() + dr.unsigned_abs() + (dq + dr).unsigned_abs()) / 2
+                    })
+                    .min()
+                    .unwrap()
+            }
+        }
+    }
+
+    // Direction of the first step toward `to`.
+    pub fn towards(&self, from: Coord, to: Coord, size: Size) -> Option<Dir> {
+        match self {
+            Self::Square(_) => from.towards(to, size),
+            Self::Hexagonal => (from != to)
+                .then(|| {
+                    [
+                        Dir::Left,
+                        Dir::Right,
+                        Dir::UpLeft,
+                        Dir::UpRight,
+                        Dir::DownLeft,
+                        Dir::DownRight,
+                    ]
+                    .into_iter()
+                    .min_by_key(|&dir| self.distance(self.step(from, dir, size).0, to, size))
+                })
+                .flatten(),
+        }
+    }
+}
+
+impl PacketSerialize for Topology {
+    fn push(&self, out: &mut Vec<u8>) {
+        let (kind, wrap) = match self {
+            Self::Square(wrap) => (0, *wrap),
+            Self::Hexagonal => (1, Wrap::Torus),
+        };
+        (kind as u8).push(out);
+        (wrap as u8).push(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Size = Size {
+        width: 8,
+        height: 6,
+    };
+
+    const TOPOLOGIES: [Topology; 4] = [
+        Topology::Square(Wrap::Torus),
+        Topology::Square(Wrap::KleinBottle),
+        Topology::Square(Wrap::ProjectivePlane),
+        Topology::Hexagonal,
+    ];
+
+    fn coords() -> impl Iterator<Item = Coord> {
+        (0..SIZE.height as usize)
+            .flat_map(|y| (0..SIZE.width as usize).map(move |x| Coord { x, y }))
+    }
+
+    #[test]
+    fn stepping_back_returns_to_the_start() {
+        for topology in TOPOLOGIES {
+            for dir in Dir::ALL
+                .into_iter()
+                .filter(|&dir| topology.allows(dir, Movement::EightWay))
+            {
+                for start in coords() {
+                    let (next, arrival) = topology.step(start, dir, SIZE);
+                    assert_eq!(
+                        topology.step(next, arrival.opposite(), SIZE),
+                        (start, dir.opposite()),
+                        "{topology:?} from {start:?} going {dir:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_are_one_step_away() {
+        for topology in [Topology::Square(Wrap::Torus), Topology::Hexagonal] {
+            for dir in Dir::ALL
+                .into_iter()
+                .filter(|&dir| topology.allows(dir, Movement::FourWay))
+            {
+                for start in coords() {
+                    let (next, _) = topology.step(start, dir, SIZE);
+                    assert_eq!(topology.distance(start, next, SIZE), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hexagonal_distance_wraps_at_the_edges() {
+        let topology = Topology::Hexagonal;
+        let corner = Coord { x: 0, y: 0 };
+        assert_eq!(topology.distance(corner, Coord { x: 7, y: 0 }, SIZE), 1);
+        assert_eq!(topology.distance(corner, Coord { x: 7, y: 5 }, SIZE), 1);
+        assert_eq!(topology.distance(corner, Coord { x: 0, y: 5 }, SIZE), 1);
+        assert_eq!(topology.distance(corner, Coord { x: 4, y: 3 }, SIZE), 5);
+    }
+}
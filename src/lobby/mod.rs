@@ -49,36 +49,33 @@ impl Lobby {
             let Message::Binary(data) = message else {
                 break;
             };
-            match data[0] {
-                0 => {
-                    let mut inner = self.inner.lock().await;
-                    let (id, game) = match inner.create(&data[1..]) {
-                        Some(res) => res,
-                        None => continue,
-                    };
-
-                    tx.lock()
-                        .await
-                        .send(Packet::GameCreated(id).message().await)
-                        .await
-                        .unwrap();
+            if data[0] == 0 {
+                let mut inner = self.inner.lock().await;
+                let (id, game) = match inner.create(&data[1..]) {
+                    Some(res) => res,
+                    None => continue,
+                };
+
+                tx.lock()
+                    .await
+                    .send(Packet::GameCreated(id).message().await)
+                    .await
+                    .unwrap();
+                inner
+                    .broadcast_message(Packet::AddGames(vec![(&id, &game)]).message().await)
+                    .await;
+
+                let inner_ref = Arc::clone(&self.inner);
+                task::spawn(async move {
+                    game.run().await;
+                    // Game is over from here.
+
+                    let mut inner = inner_ref.lock().await;
                     inner
-                        .broadcast_message(Packet::AddGames(vec![(&id, &game)]).message().await)
+                        .broadcast_message(Packet::RemoveGame(id).message().await)
                         .await;
-
-                    let inner_ref = Arc::clone(&self.inner);
-                    task::spawn(async move {
-                        game.run().await;
-                        // Game is over from here.
-
-                        let mut inner = inner_ref.lock().await;
-                        inner
-                            .broadcast_message(Packet::RemoveGame(id).message().await)
-                            .await;
-                        inner.games.remove(&id);
-                    });
-                }
-                _ => (),
+                    inner.games.remove(&id);
+                });
             }
         }
         self.inner.lock().await.users.remove(&id);
@@ -21,7 +21,7 @@ impl<'a> Packet<'a> {
             Packet::AddGames(games) => {
                 for (&id, game) in games {
                     packet![payload; id,
-                        game.name.as_bytes().len() as u8, game.name.as_bytes(),
+                        game.name.len() as u8, game.name.as_bytes(),
                         game.size,
                         game.speed,
                         game.player_count().await as u8
@@ -2,7 +2,6 @@ use std::{
     error::Error as StdError,
     net::{IpAddr, SocketAddr},
     sync::Arc,
-    u16,
 };
 
 use axum::{
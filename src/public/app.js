@@ -2,6 +2,8 @@
 
 const SPRITE_LENGTH = 16;
 const BORDER_WIDTH = 5;
+const EVENTS = ['Food rain', 'Rush', 'Portal storm', 'Walls'];
+const MAX_HANDICAP = 3;
 
 class Lobby {
     constructor() {
@@ -32,10 +34,37 @@ class Lobby {
 
     setupEvents() {
         function updateForm() {
+            document.getElementById('create-independent-bodies-group').classList.toggle('hidden', !document.getElementById('create-multi-snake').checked);
+            document.getElementById('create-diagonal-group').classList.toggle('hidden', document.getElementById('create-hexagonal').checked);
+            document.getElementById('create-mirrored-sides-group').classList.toggle('hidden', document.getElementById('create-hexagonal').checked);
+            document.getElementById('create-mirrored-ends-group').classList.toggle('hidden', document.getElementById('create-hexagonal').checked || !document.getElementById('create-mirrored-sides').checked);
+            document.getElementById('create-hill-period-group').classList.toggle('hidden', !document.getElementById('create-hill').checked);
+            document.getElementById('create-fog-radius-group').classList.toggle('hidden', !document.getElementById('create-fog').checked);
+            document.getElementById('create-sudden-death-limit-group').classList.toggle('hidden', !document.getElementById('create-sudden-death').checked);
+            document.getElementById('create-handicaps-host-group').classList.toggle('hidden', !document.getElementById('create-handicaps').checked);
+            for (const id of ['create-events-period-group', 'create-event-food-rain-group', 'create-event-rush-group', 'create-event-portal-storm-group', 'create-event-walls-group']) {
+                document.getElementById(id).classList.toggle('hidden', !document.getElementById('create-events').checked);
+            }
+            document.getElementById('create-portals-count-group').classList.toggle('hidden', !document.getElementById('create-portals').checked);
+            for (const id of ['create-teams-count-group', 'create-friendly-fire-group', 'create-team-ownership-group', 'create-capture-the-flag-group']) {
+                document.getElementById(id).classList.toggle('hidden', !document.getElementById('create-teams').checked);
+            }
+            document.getElementById('create-boost-food-group').classList.toggle('hidden', !document.getElementById('create-boost').checked);
+            document.getElementById('create-critters-count-group').classList.toggle('hidden', !document.getElementById('create-critters').checked);
+            document.getElementById('create-corpse-food-spacing-group').classList.toggle('hidden', !document.getElementById('create-corpse-food').checked);
             document.getElementById('create-perk-spacing-group').classList.toggle('hidden', Array.from(document.querySelectorAll('input[type=checkbox].perk')).every((perk) => !perk.checked));
             document.getElementById('create-speed-boost-duration-group').classList.toggle('hidden', !document.getElementById('create-speed-boost').checked);
             document.getElementById('create-food-frenzy-count-group').classList.toggle('hidden', !document.getElementById('create-food-frenzy').checked);
             document.getElementById('create-mines-trail-count-group').classList.toggle('hidden', !document.getElementById('create-mines-trail').checked);
+            document.getElementById('create-hidden-mines-group').classList.toggle('hidden', !document.getElementById('create-mines-trail').checked);
+            document.getElementById('create-shrink-percentage-group').classList.toggle('hidden', !document.getElementById('create-shrink').checked);
+            document.getElementById('create-freeze-duration-group').classList.toggle('hidden', !document.getElementById('create-freeze').checked);
+            document.getElementById('create-invert-duration-group').classList.toggle('hidden', !document.getElementById('create-invert').checked);
+            document.getElementById('create-magnet-duration-group').classList.toggle('hidden', !document.getElementById('create-magnet').checked);
+            document.getElementById('create-bomb-radius-group').classList.toggle('hidden', !document.getElementById('create-bomb').checked);
+            document.getElementById('create-blackout-duration-group').classList.toggle('hidden', !document.getElementById('create-blackout').checked);
+            document.getElementById('create-slow-others-duration-group').classList.toggle('hidden', !document.getElementById('create-slow-others').checked);
+            document.getElementById('create-big-food-side-group').classList.toggle('hidden', !document.getElementById('create-big-food').checked);
             document.querySelector('#lobby > .create > .content > .actions > .process').classList.toggle('disabled', !Array.from(document.querySelectorAll('.input:not(.hidden) > .validable')).every((elem) => elem.checkValidity()));
         }
 
@@ -61,6 +90,17 @@ class Lobby {
         document.querySelectorAll('.perk').forEach((elem) => {
             elem.addEventListener('change', updateForm);
         });
+        document.getElementById('create-corpse-food').addEventListener('change', updateForm);
+        document.getElementById('create-critters').addEventListener('change', updateForm);
+        document.getElementById('create-boost').addEventListener('change', updateForm);
+        document.getElementById('create-hexagonal').addEventListener('change', updateForm);
+        document.getElementById('create-mirrored-sides').addEventListener('change', updateForm);
+        document.getElementById('create-portals').addEventListener('change', updateForm);
+        document.getElementById('create-teams').addEventListener('change', updateForm);
+        document.getElementById('create-hill').addEventListener('change', updateForm);
+        document.getElementById('create-fog').addEventListener('change', updateForm);
+        document.getElementById('create-sudden-death').addEventListener('change', updateForm);
+        document.getElementById('create-events').addEventListener('change', updateForm);
 
         document.getElementById('tab-create').addEventListener('change', createTabSelected);
 
@@ -80,15 +120,51 @@ class Lobby {
             const width = Number(document.getElementById('create-width').value);
             const height = Number(document.getElementById('create-height').value);
             const speed = Number(document.getElementById('create-speed').value);
+            const hexagonal = document.getElementById('create-hexagonal').checked ? 1 : 0;
+            const diagonal = !hexagonal && document.getElementById('create-diagonal').checked ? 1 : 0;
+            const mirroredSides = !hexagonal && document.getElementById('create-mirrored-sides').checked;
+            const wrap = mirroredSides && document.getElementById('create-mirrored-ends').checked ? 2 : Number(mirroredSides);
+            const portals = document.getElementById('create-portals').checked ? Number(document.getElementById('create-portals-count').value) : 0;
+            const hill = document.getElementById('create-hill').checked ? Number(document.getElementById('create-hill-period').value) : 0;
+            const fog = document.getElementById('create-fog').checked ? Number(document.getElementById('create-fog-radius').value) : 0;
+            const suddenDeath = document.getElementById('create-sudden-death').checked ? Number(document.getElementById('create-sudden-death-limit').value) : 0;
+            // Automatic handicaps, or set by the host.
+            const handicaps = document.getElementById('create-handicaps').checked ? 1 + document.getElementById('create-handicaps-host').checked : 0;
+            const bounty = document.getElementById('create-bounty').checked ? 1 : 0;
+            const events = document.getElementById('create-events').checked ? Number(document.getElementById('create-events-period').value) : 0;
+            // One bit per kind of event, in the order of the server.
+            const eventKinds = ['food-rain', 'rush', 'portal-storm', 'walls']
+                .reduce((mask, kind, i) => mask | (document.getElementById(`create-event-${kind}`).checked << i), 0);
+            const teams = document.getElementById('create-teams').checked ? Number(document.getElementById('create-teams-count').value) : 0;
+            const friendlyFire = teams && document.getElementById('create-friendly-fire').checked ? 1 : 0;
+            const teamOwnership = teams && document.getElementById('create-team-ownership').checked ? 1 : 0;
+            const captureTheFlag = teams && document.getElementById('create-capture-the-flag').checked ? 1 : 0;
             const foods = Number(document.getElementById('create-foods').value);
             const foodStrength = Number(document.getElementById('create-food-strength').value);
+            const corpseFood = document.getElementById('create-corpse-food').checked ? Number(document.getElementById('create-corpse-food-spacing').value) : 0;
+            const critters = document.getElementById('create-critters').checked ? Number(document.getElementById('create-critters-count').value) : 0;
+            const boost = document.getElementById('create-boost').checked ? 1 : 0;
+            const boostFood = boost && document.getElementById('create-boost-food').checked ? 1 : 0;
             const reservedFood = document.getElementById('create-reserved-food').checked ? 1 : 0;
             const reverser = document.getElementById('create-reverser').checked ? 1 : 0;
             const teleporter = document.getElementById('create-teleporter').checked ? 1 : 0;
             const speedBoost = document.getElementById('create-speed-boost').checked ? Number(document.getElementById('create-speed-boost-duration').value) : 0;
             const foodFrenzy = document.getElementById('create-food-frenzy').checked ? Number(document.getElementById('create-food-frenzy-count').value) : 0;
             const minesTrail = document.getElementById('create-mines-trail').checked ? Number(document.getElementById('create-mines-trail-count').value) : 0;
+            const hiddenMines = minesTrail && document.getElementById('create-hidden-mines').checked ? 1 : 0;
             const multiSnake = document.getElementById('create-multi-snake').checked ? 1 : 0;
+            const independentBodies = multiSnake && document.getElementById('create-independent-bodies').checked ? 1 : 0;
+            const shrink = document.getElementById('create-shrink').checked ? Number(document.getElementById('create-shrink-percentage').value) : 0;
+            const freeze = document.getElementById('create-freeze').checked ? Number(document.getElementById('create-freeze-duration').value) : 0;
+            const invert = document.getElementById('create-invert').checked ? Number(document.getElementById('create-invert-duration').value) : 0;
+            const magnet = document.getElementById('create-magnet').checked ? Number(document.getElementById('create-magnet-duration').value) : 0;
+            const bomb = document.getElementById('create-bomb').checked ? Number(document.getElementById('create-bomb-radius').value) : 0;
+            const swapper = document.getElementById('create-swapper').checked ? 1 : 0;
+            const blackout = document.getElementById('create-blackout').checked ? Number(document.getElementById('create-blackout-duration').value) : 0;
+            const slowOthers = document.getElementById('create-slow-others').checked ? Number(document.getElementById('create-slow-others-duration').value) : 0;
+            const reverseOthers = document.getElementById('create-reverse-others').checked ? 1 : 0;
+            const bigFood = document.getElementById('create-big-food').checked ? Number(document.getElementById('create-big-food-side').value) : 0;
+            const inventory = document.getElementById('create-inventory').checked ? 1 : 0;
             const perkSpacing = document.getElementById('create-perk-spacing-group').classList.contains('hidden') ? 1 : Number(document.getElementById('create-perk-spacing').value);
 
             const nameData = new ByteBuffer(0, ByteBuffer.BIG_ENDIAN, true);
@@ -99,17 +175,50 @@ class Lobby {
             data.writeUnsignedShort(nameSize);
             data.write(nameData);
             data.writeUnsignedShort(width);
-            data.writeUnsignedShort(height);
+            // Hexagonal rows only wrap around with an even count.
+            data.writeUnsignedShort(hexagonal ? height - (height % 2) : height);
             data.writeUnsignedByte(speed);
+            data.writeUnsignedByte(diagonal);
+            data.writeUnsignedByte(hexagonal);
+            data.writeUnsignedByte(wrap);
+            data.writeUnsignedByte(portals);
+            data.writeUnsignedShort(hill);
+            data.writeUnsignedByte(fog);
+            data.writeUnsignedShort(suddenDeath);
+            data.writeUnsignedByte(handicaps);
+            data.writeUnsignedByte(bounty);
+            data.writeUnsignedShort(events);
+            data.writeUnsignedByte(eventKinds);
+            data.writeUnsignedByte(teams);
+            data.writeUnsignedByte(friendlyFire);
+            data.writeUnsignedByte(teamOwnership);
+            data.writeUnsignedByte(captureTheFlag);
             data.writeUnsignedShort(foods);
             data.writeUnsignedShort(foodStrength);
+            data.writeUnsignedByte(corpseFood);
+            data.writeUnsignedByte(critters);
+            data.writeUnsignedByte(boost);
+            data.writeUnsignedByte(boostFood);
             data.writeUnsignedByte(reservedFood);
             data.writeUnsignedByte(reverser);
             data.writeUnsignedByte(teleporter);
             data.writeUnsignedShort(speedBoost);
             data.writeUnsignedByte(foodFrenzy);
             data.writeUnsignedByte(minesTrail);
+            data.writeUnsignedByte(hiddenMines);
             data.writeUnsignedByte(multiSnake);
+            data.writeUnsignedByte(independentBodies);
+            data.writeUnsignedByte(shrink);
+            data.writeUnsignedShort(freeze);
+            data.writeUnsignedShort(invert);
+            data.writeUnsignedShort(magnet);
+            data.writeUnsignedByte(bomb);
+            data.writeUnsignedByte(swapper);
+            data.writeUnsignedShort(blackout);
+            data.writeUnsignedShort(slowOthers);
+            data.writeUnsignedByte(reverseOthers);
+            data.writeUnsignedByte(bigFood);
+            data.writeUnsignedByte(inventory);
             data.writeUnsignedShort(perkSpacing);
             this.socket.send(data.buffer);
         });
@@ -238,6 +347,11 @@ class Game {
             this.keyEventHandler = (event) => {
                 this.processKey(event);
             };
+            this.keyUpEventHandler = (event) => {
+                if (event.code === 'ShiftLeft' || event.code === 'ShiftRight') {
+                    this.socket.send(new Uint8Array([3, 0]));
+                }
+            };
             this.swipeStartEventHandler = (event) => {
                 this.touch = { x: event.touches[0].clientX, y: event.touches[0].clientY };
             };
@@ -249,6 +363,7 @@ class Game {
             };
 
             window.addEventListener('keydown', this.keyEventHandler);
+            window.addEventListener('keyup', this.keyUpEventHandler);
             window.addEventListener('touchstart', this.swipeStartEventHandler);
             window.addEventListener('touchend', this.swipeEndEventHandler);
         });
@@ -282,6 +397,54 @@ class Game {
         case 6:
             this.snakeChanges(data);
             break;
+        case 7:
+            this.movePerks(data);
+            break;
+        case 8:
+            this.removePerks(data);
+            break;
+        case 9:
+            this.updateEffects(data);
+            break;
+        case 10:
+            this.updateInventory(data);
+            break;
+        case 11:
+            this.addPortals(data);
+            break;
+        case 12:
+            this.setTeams(data);
+            break;
+        case 13:
+            this.updateTeamScores(data);
+            break;
+        case 14:
+            this.setFlags(data);
+            break;
+        case 15:
+            this.updateHill(data);
+            break;
+        case 16:
+            this.updateSight(data);
+            break;
+        case 17:
+            this.updateRound(data);
+            break;
+        case 18:
+            this.announceWinner(data);
+            break;
+        case 19:
+            this.updateHandicaps(data);
+            break;
+        case 20:
+            this.updateBounty(data);
+            break;
+        case 21:
+            this.updateEvent(data);
+            break;
+        case 22:
+            this.setWalls(data);
+            break;
         default:
             break;
         }
@@ -312,8 +475,45 @@ class Game {
         case 'KeyD':
             data = [0, 3];
             break;
+        case 'KeyQ':
+        case 'Numpad7':
+            data = [0, 4];
+            break;
+        case 'KeyE':
+        case 'Numpad9':
+            data = [0, 5];
+            break;
+        case 'KeyZ':
+        case 'Numpad1':
+            data = [0, 6];
+            break;
+        case 'KeyX':
+        case 'Numpad3':
+            data = [0, 7];
+            break;
         case 'KeyC':
-            data = [1];
+            data = this.colorMessage();
+            break;
+        case 'Space':
+        case 'Digit1':
+            data = [2, 0];
+            break;
+        case 'Digit2':
+            data = [2, 1];
+            break;
+        case 'Digit3':
+            data = [2, 2];
+            break;
+        case 'Tab':
+            event.preventDefault();
+            data = [4];
+            break;
+        case 'ShiftLeft':
+        case 'ShiftRight':
+            if (event.repeat) {
+                return;
+            }
+            data = [3, 1];
             break;
         default:
             return;
@@ -326,8 +526,27 @@ class Game {
             width: data.readUnsignedShort(),
             height: data.readUnsignedShort(),
         };
+        const nameLength = data.readUnsignedByte();
+        const name = data.readString(nameLength);
+        this.selfId = data.readUnsignedShort();
+        this.hexagonal = data.readUnsignedByte() === 1;
+        this.wrap = data.readUnsignedByte();
         this.players = {};
         this.perks = {};
+        // Cells covered by multi-cell perks, pointing to the key of their top left corner.
+        this.perkParts = {};
+        // Cells of the other snakes seen through the fog of war.
+        this.glimpses = {};
+        this.portals = [];
+        this.teams = null;
+        this.flags = null;
+        this.hill = null;
+        this.round = null;
+        this.handicaps = {};
+        this.handicapHost = null;
+        this.bounty = null;
+        this.walls = [];
+        this.event = null;
 
         this.canvas = document.createElement('canvas');
         this.context = this.canvas.getContext('2d');
@@ -341,7 +560,9 @@ class Game {
                 Math.floor(mainSize.width / this.size.width),
                 Math.floor((mainSize.height + additionalHeight) / this.size.height),
             );
-            this.canvas.width = this.size.width * this.cellSize + 2 * BORDER_WIDTH;
+            // Odd rows of hexagonal grids are shifted half a cell to the right.
+            this.canvas.width = (this.size.width + (this.hexagonal ? 0.5 : 0)) * this.cellSize
+                + 2 * BORDER_WIDTH;
             this.canvas.height = this.size.height * this.cellSize + 2 * BORDER_WIDTH;
 
             const scale = Math.min(
@@ -359,10 +580,6 @@ class Game {
         this.resizeHandler(87);
         window.addEventListener('resize', this.resizeHandler);
 
-        const nameLength = data.readUnsignedByte();
-        const name = data.readString(nameLength);
-        this.selfId = data.readUnsignedShort();
-
         const header = document.createElement('div');
         header.classList.add('header');
 
@@ -373,13 +590,37 @@ class Game {
         const actions = document.createElement('div');
         actions.classList.add('actions');
 
+        this.inventory = document.createElement('div');
+        this.inventory.classList.add('inventory');
+
+        this.effects = document.createElement('div');
+        this.effects.classList.add('effects');
+
         this.changeColor = document.createElement('div');
         this.changeColor.classList.add('action', 'change-color', 'hidden');
         this.changeColor.title = 'Change color';
         this.changeColor.addEventListener('click', () => {
-            this.socket.send(new Uint8Array([1]));
+            this.socket.send(new Uint8Array(this.colorMessage()));
         });
 
+        this.scores = document.createElement('div');
+        this.scores.classList.add('scores');
+
+        this.handicapLevels = document.createElement('div');
+        this.handicapLevels.classList.add('scores');
+
+        this.hillScore = document.createElement('div');
+        this.hillScore.classList.add('hill', 'hidden');
+
+        this.roundState = document.createElement('div');
+        this.roundState.classList.add('round', 'hidden');
+
+        this.bountyState = document.createElement('div');
+        this.bountyState.classList.add('bounty', 'hidden');
+
+        this.eventState = document.createElement('div');
+        this.eventState.classList.add('event', 'hidden');
+
         const leave = document.createElement('div');
         leave.classList.add('action', 'leave');
         leave.innerText = 'Leave';
@@ -390,15 +631,31 @@ class Game {
             this.leave();
         });
 
-        actions.append(this.changeColor, leave);
+        actions.append(
+            this.roundState,
+            this.eventState,
+            this.bountyState,
+            this.hillScore,
+            this.handicapLevels,
+            this.scores,
+            this.effects,
+            this.inventory,
+            this.changeColor,
+            leave,
+        );
         header.append(title, actions);
         document.getElementById('game').append(header, this.canvas);
         document.body.classList.replace('lobbying', 'playing');
     }
 
     leave() {
+        clearInterval(this.roundTimer);
+        this.roundTimer = undefined;
+        clearInterval(this.eventTimer);
+        this.eventTimer = undefined;
         window.removeEventListener('resize', this.resizeHandler);
         window.removeEventListener('keydown', this.keyEventHandler);
+        window.removeEventListener('keyup', this.keyUpEventHandler);
         window.removeEventListener('touchstart', this.swipeStartEventHandler);
         window.removeEventListener('touchend', this.swipeEndEventHandler);
 
@@ -412,6 +669,8 @@ class Game {
     redrawCanvas() {
         this.drawBorders();
         this.emptyCanvas();
+        this.drawBases();
+        this.drawHill();
 
         for (const player of Object.values(this.players)) {
             for (const body of Object.values(player.bodies)) {
@@ -420,20 +679,44 @@ class Game {
                 }
             }
         }
+        for (const glimpse of Object.values(this.glimpses)) {
+            this.drawGlimpse(glimpse);
+        }
         for (const perk of Object.values(this.perks)) {
             this.drawPerk(perk);
         }
+        for (const portal of this.portals) {
+            this.drawPortal(portal);
+        }
+        for (const wall of this.walls) {
+            this.drawWall(wall);
+        }
+        this.drawGroundFlags();
+        this.drawCarriedFlags();
+        this.drawBountyMarks();
     }
 
     drawBorders() {
         this.context.strokeStyle = '#ffffff';
         this.context.lineWidth = BORDER_WIDTH;
-        this.context.strokeRect(
-            BORDER_WIDTH,
-            BORDER_WIDTH,
-            this.canvas.width - 2 * BORDER_WIDTH,
-            this.canvas.height - 2 * BORDER_WIDTH,
-        );
+        const left = BORDER_WIDTH;
+        const top = BORDER_WIDTH;
+        const right = this.canvas.width - BORDER_WIDTH;
+        const bottom = this.canvas.height - BORDER_WIDTH;
+        // Mirrored edges are dashed.
+        for (const [mirrored, edges] of [
+            [this.wrap >= 1, [[left, top, left, bottom], [right, top, right, bottom]]],
+            [this.wrap === 2, [[left, top, right, top], [left, bottom, right, bottom]]],
+        ]) {
+            this.context.setLineDash(mirrored ? [this.cellSize, this.cellSize] : []);
+            for (const [fromX, fromY, toX, toY] of edges) {
+                this.context.beginPath();
+                this.context.moveTo(fromX, fromY);
+                this.context.lineTo(toX, toY);
+                this.context.stroke();
+            }
+        }
+        this.context.setLineDash([]);
     }
 
     emptyCanvas() {
@@ -449,7 +732,12 @@ class Game {
         while (data.available) {
             const playerId = data.readUnsignedShort();
             const color = data.readUnsignedShort();
+            const selected = data.readUnsignedShort();
             const frames = this.generateFrames(color);
+            // Bodies are sent again after a swap with the fog of war.
+            for (const body of Object.values(this.players[playerId]?.bodies ?? {})) {
+                this.clearCell(body);
+            }
             const nbBody = data.readUnsignedByte();
             const bodies = {};
             for (let b = 0; b < nbBody; b += 1) {
@@ -462,6 +750,7 @@ class Game {
                         y: data.readUnsignedShort(),
                     };
                     body.push(cell);
+                    delete this.glimpses[`${cell.x},${cell.y}`];
                 }
                 bodies[bodyId] = body;
 
@@ -469,10 +758,13 @@ class Game {
                     this.drawFrame(body, i, frames);
                 }
             }
-            this.players[playerId] = { color, bodies, frames: this.generateFrames(color) };
+            this.players[playerId] = {
+                color, bodies, selected, frames: this.generateFrames(color),
+            };
 
             if (playerId === this.selfId) {
                 this.updateChangeColorButton(color);
+                this.markSelected(this.players[playerId]);
             }
         }
     }
@@ -486,7 +778,9 @@ class Game {
             x: data.readUnsignedShort(),
             y: data.readUnsignedShort(),
         }];
-        this.players[playerId] = { color, bodies, frames: this.generateFrames(color) };
+        this.players[playerId] = {
+            color, bodies, selected: bodyId, frames: this.generateFrames(color),
+        };
         this.drawFrame(bodies[bodyId], 0, this.players[playerId].frames);
     }
 
@@ -495,7 +789,21 @@ class Game {
         for (const body of Object.values(this.players[playerId].bodies)) {
             this.clearCell(body);
         }
+        for (const [key, glimpse] of Object.entries(this.glimpses)) {
+            if (glimpse.playerId === playerId) {
+                delete this.glimpses[key];
+                this.clearCell(glimpse.coord);
+            }
+        }
         delete this.players[playerId];
+        if (this.bounty?.leader === playerId) {
+            this.bounty.leader = null;
+            this.refreshBounty();
+        }
+        if (this.handicaps[playerId] !== undefined) {
+            delete this.handicaps[playerId];
+            this.drawHandicaps();
+        }
     }
 
     changePlayerColor(data) {
@@ -512,9 +820,18 @@ class Game {
                 this.drawFrame(body, i, player.frames);
             }
         }
+        for (const glimpse of Object.values(this.glimpses)) {
+            if (glimpse.playerId === playerId) {
+                this.drawGlimpse(glimpse);
+            }
+        }
         if (playerId === this.selfId) {
             this.updateChangeColorButton(color);
         }
+        this.drawHandicaps();
+        if (this.bounty !== null) {
+            this.refreshBounty();
+        }
     }
 
     updateChangeColorButton(color) {
@@ -539,12 +856,20 @@ class Game {
                     y: data.readUnsignedShort(),
                 };
                 body.unshift(head);
+                const anchor = this.perkParts[`${head.x},${head.y}`];
+                if (anchor !== undefined) {
+                    this.removePerk(anchor, head);
+                }
                 delete this.perks[`${head.x},${head.y}`];
+                delete this.glimpses[`${head.x},${head.y}`];
 
                 if (body.length >= 2) {
                     this.drawFrame(body, 1, player.frames);
                 }
                 this.drawFrame(body, 0, player.frames);
+                if (player === this.players[this.selfId]) {
+                    this.markSelected(player);
+                }
             } break;
             case 2: {
                 const player = this.players[data.readUnsignedShort()];
@@ -554,6 +879,7 @@ class Game {
                     y: data.readUnsignedShort(),
                 };
                 player.bodies[bodyId] = [head];
+                delete this.glimpses[`${head.x},${head.y}`];
                 this.drawFrame(player.bodies[bodyId], 0, player.frames);
             } break;
             case 3: {
@@ -571,10 +897,53 @@ class Game {
                     this.drawFrame(body, body.length - 1, player.frames);
                 }
             } break;
+            case 5: {
+                const player = this.players[data.readUnsignedShort()];
+                const bodyId = data.readUnsignedShort();
+                const other = this.players[data.readUnsignedShort()];
+                const otherBodyId = data.readUnsignedShort();
+                const body = player.bodies[bodyId];
+                player.bodies[bodyId] = other.bodies[otherBodyId];
+                other.bodies[otherBodyId] = body;
+                const swaps = [[player, player.bodies[bodyId]], [other, body]];
+                for (const [{ frames }, swapped] of swaps) {
+                    for (let i = 0; i < swapped.length; i += 1) {
+                        this.drawFrame(swapped, i, frames);
+                    }
+                }
+            } break;
+            case 6: {
+                const player = this.players[data.readUnsignedShort()];
+                const previous = player.bodies[player.selected];
+                if (previous !== undefined && previous.length > 0) {
+                    this.drawFrame(previous, 0, player.frames);
+                }
+                player.selected = data.readUnsignedShort();
+                if (player === this.players[this.selfId]) {
+                    this.markSelected(player);
+                }
+            } break;
             default:
                 break;
             }
         }
+        this.drawCarriedFlags();
+        this.drawBountyMarks();
+    }
+
+    markSelected(player) {
+        const body = player.bodies[player.selected];
+        if (Object.keys(player.bodies).length < 2 || body === undefined || body.length === 0) {
+            return;
+        }
+        this.context.strokeStyle = '#ffffff';
+        this.context.lineWidth = 2;
+        this.context.strokeRect(
+            this.cellLeft(body[0]) + 1,
+            this.cellTop(body[0]) + 1,
+            this.cellSize - 2,
+            this.cellSize - 2,
+        );
     }
 
     addPerks(data) {
@@ -590,6 +959,12 @@ class Game {
             case 7:
                 perk.owner = data.readUnsignedShort();
                 break;
+            case 19:
+                perk.side = data.readUnsignedByte();
+                for (const part of this.perkCells(perk)) {
+                    this.perkParts[`${part.x},${part.y}`] = `${coord.x},${coord.y}`;
+                }
+                break;
             default:
                 break;
             }
@@ -598,20 +973,672 @@ class Game {
         }
     }
 
+    movePerks(data) {
+        while (data.available) {
+            const from = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            const to = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            const perk = this.perks[`${from.x},${from.y}`];
+            if (perk === undefined) {
+                continue;
+            }
+            delete this.perks[`${from.x},${from.y}`];
+            this.clearCell(from);
+            perk.coord = to;
+            this.perks[`${to.x},${to.y}`] = perk;
+            this.drawPerk(perk);
+        }
+    }
+
+    // Each packet holds every portal, storms open and close some during the game.
+    addPortals(data) {
+        this.clearCell(this.portals.map((portal) => portal.coord));
+        this.portals = [];
+        while (data.available) {
+            // Both ends of a pair share a color.
+            const color = (this.portals.length * 97) % 360;
+            for (let i = 0; i < 2; i += 1) {
+                const portal = {
+                    coord: {
+                        x: data.readUnsignedShort(),
+                        y: data.readUnsignedShort(),
+                    },
+                    color,
+                };
+                this.portals.push(portal);
+                this.drawPortal(portal);
+            }
+        }
+    }
+
+    setWalls(data) {
+        this.clearCell(this.walls);
+        this.walls = [];
+        while (data.available) {
+            const wall = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            this.walls.push(wall);
+            this.drawWall(wall);
+        }
+    }
+
+    drawWall(wall) {
+        this.context.fillStyle = '#8a8a8a';
+        this.context.fillRect(
+            this.cellLeft(wall),
+            this.cellTop(wall),
+            this.cellSize,
+            this.cellSize,
+        );
+    }
+
+    drawPortal(portal) {
+        this.context.strokeStyle = `hsl(${portal.color}, 100%, 60%)`;
+        this.context.lineWidth = Math.max(2, this.cellSize / 6);
+        this.context.beginPath();
+        this.context.arc(
+            this.cellLeft(portal.coord) + this.cellSize / 2,
+            this.cellTop(portal.coord) + this.cellSize / 2,
+            this.cellSize / 3,
+            0,
+            2 * Math.PI,
+        );
+        this.context.stroke();
+    }
+
+    perkCells(perk) {
+        const cells = [];
+        for (let dy = 0; dy < (perk.side ?? 1); dy += 1) {
+            for (let dx = 0; dx < (perk.side ?? 1); dx += 1) {
+                cells.push({ x: perk.coord.x + dx, y: perk.coord.y + dy });
+            }
+        }
+        return cells;
+    }
+
+    // Clear all the cells of a perk, but the one a head just entered.
+    // Everything but our own snakes.
+    dropBoard() {
+        for (const key of Object.keys(this.perks)) {
+            this.removePerk(key);
+        }
+        for (const glimpse of Object.values(this.glimpses)) {
+            this.clearCell(glimpse.coord);
+        }
+        this.glimpses = {};
+        for (const [playerId, player] of Object.entries(this.players)) {
+            if (Number(playerId) !== this.selfId) {
+                for (const body of Object.values(player.bodies)) {
+                    this.clearCell(body);
+                }
+                player.bodies = {};
+            }
+        }
+    }
+
+    removePerk(key, head) {
+        const perk = this.perks[key];
+        if (perk === undefined) {
+            return;
+        }
+        delete this.perks[key];
+        for (const cell of this.perkCells(perk)) {
+            delete this.perkParts[`${cell.x},${cell.y}`];
+            if (head === undefined || cell.x !== head.x || cell.y !== head.y) {
+                this.clearCell(cell);
+            }
+        }
+    }
+
+    updateSight(data) {
+        const hidden = data.readUnsignedShort();
+        for (let i = 0; i < hidden; i += 1) {
+            const coord = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            if (this.glimpses[`${coord.x},${coord.y}`] !== undefined) {
+                delete this.glimpses[`${coord.x},${coord.y}`];
+                this.clearCell(coord);
+            }
+        }
+        while (data.available) {
+            const coord = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            const glimpse = { coord, playerId: data.readUnsignedShort() };
+            this.glimpses[`${coord.x},${coord.y}`] = glimpse;
+            this.drawGlimpse(glimpse);
+        }
+    }
+
+    drawGlimpse(glimpse) {
+        const player = this.players[glimpse.playerId];
+        if (player === undefined) {
+            return;
+        }
+        this.drawFrame([glimpse.coord], 0, player.frames);
+    }
+
+    removePerks(data) {
+        while (data.available) {
+            const coord = {
+                x: data.readUnsignedShort(),
+                y: data.readUnsignedShort(),
+            };
+            if (this.perks[`${coord.x},${coord.y}`] !== undefined) {
+                this.removePerk(`${coord.x},${coord.y}`);
+            }
+        }
+    }
+
+    updateEffects(data) {
+        while (data.available) {
+            const playerId = data.readUnsignedShort();
+            const effects = {};
+            for (const name of ['Speed', 'Frozen', 'Inverted', 'Magnet', 'Mines', 'Blackout', 'Slowed']) {
+                effects[name] = data.readUnsignedShort();
+            }
+            const player = this.players[playerId];
+            if (player !== undefined) {
+                player.effects = effects;
+            }
+            if (playerId !== this.selfId) {
+                continue;
+            }
+
+            // The server holds the board back during a blackout and sends it again once over.
+            if (effects.Blackout > 0 && !this.canvas.classList.contains('blackout')) {
+                this.dropBoard();
+            }
+            this.canvas.classList.toggle('blackout', effects.Blackout > 0);
+            this.effects.replaceChildren(
+                ...Object.entries(effects)
+                    .filter(([, remaining]) => remaining > 0)
+                    .map(([name, remaining]) => {
+                        const effect = document.createElement('div');
+                        effect.classList.add('effect');
+                        effect.innerText = `${name} ${remaining}`;
+                        return effect;
+                    }),
+            );
+        }
+    }
+
+    setTeams(data) {
+        this.teams = {
+            sharedOwnership: data.readUnsignedByte() === 1,
+            members: {},
+            count: this.teams?.count ?? 0,
+        };
+        while (data.available) {
+            const playerId = data.readUnsignedShort();
+            this.teams.members[playerId] = data.readUnsignedByte();
+        }
+        this.changeColor.title = 'Switch team';
+        this.redrawCanvas();
+    }
+
+    updateTeamScores(data) {
+        const scores = [];
+        while (data.available) {
+            scores.push(data.readUnsignedShort());
+        }
+        if (this.teams !== null) {
+            this.teams.count = scores.length;
+        }
+        this.scores.replaceChildren(
+            ...scores.map((score, team) => {
+                const element = document.createElement('div');
+                element.classList.add('score');
+                element.style.backgroundColor = `hsl(${(team * 360) / scores.length}, 100%, 35%)`;
+                element.innerText = score;
+                return element;
+            }),
+        );
+    }
+
+    updateBounty(data) {
+        const hasLeader = data.readUnsignedByte() === 1;
+        const leader = data.readUnsignedShort();
+        const reward = data.readUnsignedShort();
+        const scores = {};
+        while (data.available) {
+            const playerId = data.readUnsignedShort();
+            scores[playerId] = data.readUnsignedShort();
+        }
+        // Repaint the heads of the previous leader without the mark.
+        const previous = this.players[this.bounty?.leader];
+        if (previous !== undefined) {
+            for (const body of Object.values(previous.bodies)) {
+                if (body.length > 0) {
+                    this.drawFrame(body, 0, previous.frames);
+                }
+            }
+        }
+        this.bounty = { leader: hasLeader ? leader : null, reward, scores };
+        this.refreshBounty();
+        this.drawBountyMarks();
+    }
+
+    refreshBounty() {
+        const { leader, reward, scores } = this.bounty;
+        const player = this.players[leader];
+        this.bountyState.classList.remove('hidden');
+        this.bountyState.style.backgroundColor = player !== undefined ? `hsl(${player.color}, 100%, 35%)` : '';
+        let text = 'No bounty';
+        if (leader === this.selfId) {
+            text = `Wanted +${reward}`;
+        } else if (player !== undefined) {
+            text = `Bounty +${reward}`;
+        }
+        const claimed = scores[this.selfId] ?? 0;
+        this.bountyState.innerText = claimed > 0 ? `${text} · ${claimed} claimed` : text;
+    }
+
+    drawBountyMarks() {
+        const player = this.players[this.bounty?.leader];
+        if (player === undefined) {
+            return;
+        }
+        this.context.strokeStyle = '#ffd700';
+        this.context.lineWidth = Math.max(2, this.cellSize / 8);
+        for (const body of Object.values(player.bodies)) {
+            if (body.length === 0) {
+                continue;
+            }
+            this.context.beginPath();
+            this.context.arc(
+                this.cellLeft(body[0]) + this.cellSize / 2,
+                this.cellTop(body[0]) + this.cellSize / 2,
+                this.cellSize / 2 - this.context.lineWidth / 2,
+                0,
+                2 * Math.PI,
+            );
+            this.context.stroke();
+        }
+    }
+
+    updateHandicaps(data) {
+        const hasHost = data.readUnsignedByte() > 0;
+        const host = data.readUnsignedShort();
+        this.handicapHost = hasHost ? host : null;
+        this.handicaps = {};
+        while (data.available) {
+            const playerId = data.readUnsignedShort();
+            this.handicaps[playerId] = data.readUnsignedByte();
+        }
+        this.drawHandicaps();
+    }
+
+    // The host sees every player and clicks them to cycle their level.
+    drawHandicaps() {
+        const isHost = this.handicapHost === this.selfId;
+        this.handicapLevels.replaceChildren(
+            ...Object.keys(this.players)
+                .map((playerId) => [Number(playerId), this.handicaps[playerId] ?? 0])
+                .filter(([, level]) => isHost || level > 0)
+                .map(([playerId, level]) => {
+                    const element = document.createElement('div');
+                    element.classList.add('score');
+                    element.style.backgroundColor = `hsl(${this.players[playerId].color}, 100%, 35%)`;
+                    element.title = playerId === this.selfId ? 'Your handicap' : 'Handicap';
+                    element.innerText = `+${level}`;
+                    if (isHost) {
+                        element.classList.add('settable');
+                        element.title += ', click to change it';
+                        element.addEventListener('click', () => {
+                            const next = (level + 1) % (MAX_HANDICAP + 1);
+                            const message = [6, playerId >> 8, playerId & 255, next];
+                            this.socket.send(new Uint8Array(message));
+                        });
+                    }
+                    return element;
+                }),
+        );
+    }
+
+    setFlags(data) {
+        const radius = data.readUnsignedByte();
+        const list = [];
+        while (data.available) {
+            const flag = {
+                team: data.readUnsignedByte(),
+                home: {
+                    x: data.readUnsignedShort(),
+                    y: data.readUnsignedShort(),
+                },
+            };
+            if (data.readUnsignedByte() === 0) {
+                flag.coord = {
+                    x: data.readUnsignedShort(),
+                    y: data.readUnsignedShort(),
+                };
+            } else {
+                flag.carrier = {
+                    playerId: data.readUnsignedShort(),
+                    bodyId: data.readUnsignedShort(),
+                };
+            }
+            list.push(flag);
+        }
+        this.flags = { radius, list };
+        this.redrawCanvas();
+    }
+
+    teamHue(team) {
+        return (team * 360) / this.flags.list.length;
+    }
+
+    inBase(flag, { x, y }) {
+        const dx = Math.min(x.absDiff(flag.home.x), this.size.width - x.absDiff(flag.home.x));
+        const dy = Math.min(y.absDiff(flag.home.y), this.size.height - y.absDiff(flag.home.y));
+        return dx <= this.flags.radius && dy <= this.flags.radius;
+    }
+
+    drawBaseCell(flag, coord) {
+        this.context.fillStyle = `hsla(${this.teamHue(flag.team)}, 100%, 35%, 0.3)`;
+        this.context.fillRect(
+            this.cellLeft(coord),
+            this.cellTop(coord),
+            this.cellSize,
+            this.cellSize,
+        );
+    }
+
+    drawBases() {
+        if (this.flags === null) {
+            return;
+        }
+        const { radius } = this.flags;
+        for (const flag of this.flags.list) {
+            for (let dy = -radius; dy <= radius; dy += 1) {
+                for (let dx = -radius; dx <= radius; dx += 1) {
+                    this.drawBaseCell(flag, {
+                        x: (flag.home.x + dx + this.size.width) % this.size.width,
+                        y: (flag.home.y + dy + this.size.height) % this.size.height,
+                    });
+                }
+            }
+        }
+    }
+
+    drawFlag(flag, coord) {
+        const left = this.cellLeft(coord);
+        const top = this.cellTop(coord);
+        this.context.fillStyle = '#ffffff';
+        this.context.fillRect(
+            left + this.cellSize / 4,
+            top + this.cellSize / 8,
+            Math.max(1, this.cellSize / 10),
+            (this.cellSize * 3) / 4,
+        );
+        this.context.fillStyle = `hsl(${this.teamHue(flag.team)}, 100%, 50%)`;
+        this.context.beginPath();
+        this.context.moveTo(left + this.cellSize / 4, top + this.cellSize / 8);
+        this.context.lineTo(left + (this.cellSize * 7) / 8, top + (this.cellSize * 5) / 16);
+        this.context.lineTo(left + this.cellSize / 4, top + this.cellSize / 2);
+        this.context.fill();
+    }
+
+    drawGroundFlags() {
+        for (const flag of this.flags?.list ?? []) {
+            if (flag.coord !== undefined) {
+                this.drawFlag(flag, flag.coord);
+            }
+        }
+    }
+
+    // Carried flags are shown on the head of the carrying body.
+    drawCarriedFlags() {
+        for (const flag of this.flags?.list ?? []) {
+            if (flag.carrier === undefined) {
+                continue;
+            }
+            const body = this.players[flag.carrier.playerId]?.bodies[flag.carrier.bodyId];
+            if (body !== undefined && body.length > 0) {
+                this.drawFlag(flag, body[0]);
+            }
+        }
+    }
+
+    updateRound(data) {
+        const suddenDeath = data.readUnsignedByte() === 1;
+        const remaining = data.readUnsignedShort();
+        this.round = {
+            ...this.round,
+            suddenDeath,
+            deadline: Date.now() + remaining * 1000,
+        };
+        if (this.roundTimer === undefined) {
+            this.roundTimer = setInterval(() => this.refreshRound(), 250);
+        }
+        this.refreshRound();
+    }
+
+    announceWinner(data) {
+        const winner = data.available ? data.readUnsignedShort() : null;
+        let text = 'Draw';
+        if (winner === this.selfId) {
+            text = 'You win!';
+        } else if (winner !== null) {
+            text = 'Round lost';
+        }
+        this.round = {
+            ...this.round,
+            announcement: text,
+            color: this.players[winner]?.color,
+            announcedUntil: Date.now() + 5000,
+        };
+        this.refreshRound();
+    }
+
+    refreshRound() {
+        const { suddenDeath, deadline, announcement, color, announcedUntil } = this.round;
+        this.roundState.classList.remove('hidden');
+        if (announcedUntil !== undefined && Date.now() < announcedUntil) {
+            this.roundState.classList.remove('sudden-death');
+            this.roundState.style.backgroundColor = color !== undefined ? `hsl(${color}, 100%, 35%)` : '';
+            this.roundState.innerText = announcement;
+            return;
+        }
+        this.roundState.style.backgroundColor = '';
+        this.roundState.classList.toggle('sudden-death', suddenDeath);
+        if (suddenDeath) {
+            this.roundState.innerText = 'Sudden death';
+        } else {
+            const seconds = Math.max(0, Math.ceil((deadline - Date.now()) / 1000));
+            this.roundState.innerText = `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, '0')}`;
+        }
+    }
+
+    updateEvent(data) {
+        const kind = data.readUnsignedByte();
+        const duration = data.readUnsignedShort();
+        // Lasting events are announced again with no duration once over.
+        if (duration === 0 && EVENTS[kind] !== 'Food rain') {
+            this.event = null;
+        } else {
+            this.event = {
+                name: EVENTS[kind],
+                deadline: Date.now() + (duration || 5) * 1000,
+                lasting: duration > 0,
+            };
+        }
+        if (this.eventTimer === undefined) {
+            this.eventTimer = setInterval(() => this.refreshEvent(), 250);
+        }
+        this.refreshEvent();
+    }
+
+    refreshEvent() {
+        const seconds = this.event !== null
+            ? Math.ceil((this.event.deadline - Date.now()) / 1000)
+            : 0;
+        this.eventState.classList.toggle('hidden', seconds <= 0);
+        if (seconds > 0) {
+            this.eventState.innerText = this.event.lasting ? `${this.event.name} · ${seconds}s` : `${this.event.name}!`;
+        }
+    }
+
+    updateHill(data) {
+        const center = {
+            x: data.readUnsignedShort(),
+            y: data.readUnsignedShort(),
+        };
+        const radius = data.readUnsignedByte();
+        const state = data.readUnsignedByte();
+        const holder = data.readUnsignedShort();
+        const scores = {};
+        while (data.available) {
+            const playerId = data.readUnsignedShort();
+            scores[playerId] = data.readUnsignedShort();
+        }
+        const moved = this.hill === null
+            || this.hill.center.x !== center.x
+            || this.hill.center.y !== center.y;
+        this.hill = {
+            center, radius, state, holder, scores,
+        };
+        if (moved) {
+            this.redrawCanvas();
+        }
+
+        const best = Math.max(0, ...Object.values(scores));
+        const holderColor = this.players[holder]?.color;
+        this.hillScore.classList.remove('hidden');
+        this.hillScore.style.backgroundColor = state === 1 && holderColor !== undefined ? `hsl(${holderColor}, 100%, 35%)` : '';
+        this.hillScore.innerText = `Hill ${scores[this.selfId] ?? 0} / ${best}${state === 2 ? ' contested' : ''}`;
+    }
+
+    inHill({ x, y }) {
+        const { center, radius } = this.hill;
+        const dx = Math.min(x.absDiff(center.x), this.size.width - x.absDiff(center.x));
+        const dy = Math.min(y.absDiff(center.y), this.size.height - y.absDiff(center.y));
+        return dx <= radius && dy <= radius;
+    }
+
+    drawHillCell(coord) {
+        this.context.fillStyle = 'rgba(255, 215, 0, 0.2)';
+        this.context.fillRect(
+            this.cellLeft(coord),
+            this.cellTop(coord),
+            this.cellSize,
+            this.cellSize,
+        );
+    }
+
+    drawHill() {
+        if (this.hill === null) {
+            return;
+        }
+        const { center, radius } = this.hill;
+        for (let dy = -radius; dy <= radius; dy += 1) {
+            for (let dx = -radius; dx <= radius; dx += 1) {
+                this.drawHillCell({
+                    x: (center.x + dx + this.size.width) % this.size.width,
+                    y: (center.y + dy + this.size.height) % this.size.height,
+                });
+            }
+        }
+    }
+
+    // In team mode, colors are those of the teams and the button switches to the next one.
+    colorMessage() {
+        if (this.teams === null) {
+            return [1];
+        }
+        const team = this.teams.members[this.selfId] ?? 0;
+        return [5, (team + 1) % Math.max(this.teams.count, 1)];
+    }
+
+    // Reserved foods and mines may belong to the whole team.
+    isAlly(playerId) {
+        if (playerId === this.selfId) {
+            return true;
+        }
+        return this.teams !== null
+            && this.teams.sharedOwnership
+            && this.teams.members[playerId] !== undefined
+            && this.teams.members[playerId] === this.teams.members[this.selfId];
+    }
+
+    updateInventory(data) {
+        this.inventory.replaceChildren();
+        for (let slot = 0; data.available; slot += 1) {
+            const perk = { id: data.readUnsignedByte() };
+            if (perk.id === 1 || perk.id === 7) {
+                perk.owner = data.readUnsignedShort();
+            }
+            const style = this.perkStyle(perk);
+            if (style === null) {
+                continue;
+            }
+
+            const item = document.createElement('canvas');
+            item.classList.add('item');
+            item.title = `Press ${slot + 1} to activate`;
+            item.width = 28;
+            item.height = 28;
+            const context = item.getContext('2d');
+            context.fillStyle = style.color;
+            context.beginPath();
+            context.arc(14, 14, 14, 0, 2 * Math.PI);
+            context.fill();
+            context.fillStyle = '#ffffff';
+            context.setTransform(14 / style.iconWidth, 0, 0, 14 / style.iconHeight, 7, 7);
+            context.fill(new Path2D(style.icon));
+            this.inventory.append(item);
+        }
+    }
+
+    cellLeft({ x, y }) {
+        const shift = this.hexagonal && y % 2 === 1 ? Math.floor(this.cellSize / 2) : 0;
+        return BORDER_WIDTH + x * this.cellSize + shift;
+    }
+
+    cellTop({ y }) {
+        return BORDER_WIDTH + y * this.cellSize;
+    }
+
     clearCell(coords) {
-        for (const { x, y } of coords instanceof Array ? coords : [coords]) {
+        for (const coord of coords instanceof Array ? coords : [coords]) {
             this.context.clearRect(
-                BORDER_WIDTH + x * this.cellSize,
-                BORDER_WIDTH + y * this.cellSize,
+                this.cellLeft(coord),
+                this.cellTop(coord),
                 this.cellSize,
                 this.cellSize,
             );
+            // Zones and flags lie under everything else.
+            if (this.hill !== null && this.inHill(coord)) {
+                this.drawHillCell(coord);
+            }
+            if (this.flags !== null) {
+                const base = this.flags.list.find((flag) => this.inBase(flag, coord));
+                if (base !== undefined) {
+                    this.drawBaseCell(base, coord);
+                }
+                const flag = this.flags.list
+                    .find((f) => f.coord?.x === coord.x && f.coord?.y === coord.y);
+                if (flag !== undefined) {
+                    this.drawFlag(flag, coord);
+                }
+            }
         }
     }
 
     drawFrame(body, index, frames) {
         let frameIndex;
-        if (index === 0 && body.length === 1) {
+        // Sprites only connect square cells, hexagonal snakes are drawn as chains of dots.
+        if ((index === 0 && body.length === 1) || this.hexagonal) {
             frameIndex = 0;
         } else {
             const forw = body[index - 1] ?? null;
@@ -645,23 +1672,24 @@ class Game {
         this.clearCell(body[index]);
         this.context.putImageData(
             frames[frameIndex],
-            BORDER_WIDTH + body[index].x * this.cellSize,
-            BORDER_WIDTH + body[index].y * this.cellSize,
+            this.cellLeft(body[index]),
+            this.cellTop(body[index]),
         );
     }
 
-    drawPerk(perk) {
+    perkStyle(perk) {
         let color;
         let icon;
         let iconWidth = 28;
         const iconHeight = 28;
         switch (perk.id) {
         case 0: // Food
+        case 19: // Big food
             color = '#2fbf71';
             icon = 'M1 15.8727C1 17.4144 2.05445 18.4476 3.60577 18.4476H8.69171V23.5431C8.69171 25.0965 9.72272 26.1372 11.2644 26.1372H15.8844C17.4261 26.1372 18.4593 25.0965 18.4593 23.5431V18.4476H23.5548C25.1083 18.4476 26.1489 17.4144 26.1489 15.8727V11.2527C26.1489 9.71101 25.1083 8.67789 23.5548 8.67789H18.4593V3.59405C18.4593 2.04273 17.4261 1 15.8844 1H11.2644C9.72272 1 8.69171 2.04273 8.69171 3.59405V8.67789H3.59405C2.04273 8.67789 1 9.71101 1 11.2527V15.8727Z';
             break;
         case 1: // Reserved food
-            color = perk.owner === this.selfId ? '#1e90ff' : '#0c3b66';
+            color = this.isAlly(perk.owner) ? '#1e90ff' : '#0c3b66';
             icon = 'M1 15.8727C1 17.4144 2.05445 18.4476 3.60577 18.4476H8.69171V23.5431C8.69171 25.0965 9.72272 26.1372 11.2644 26.1372H15.8844C17.4261 26.1372 18.4593 25.0965 18.4593 23.5431V18.4476H23.5548C25.1083 18.4476 26.1489 17.4144 26.1489 15.8727V11.2527C26.1489 9.71101 25.1083 8.67789 23.5548 8.67789H18.4593V3.59405C18.4593 2.04273 17.4261 1 15.8844 1H11.2644C9.72272 1 8.69171 2.04273 8.69171 3.59405V8.67789H3.59405C2.04273 8.67789 1 9.71101 1 11.2527V15.8727Z';
             break;
         case 2: // Reverser
@@ -686,21 +1714,76 @@ class Game {
             icon = 'M13.3923 25.6767C19.698 25.6767 23.9031 21.4235 23.9031 15.0239C23.9031 4.42804 14.8448 0 8.59913 0C7.39843 0 6.61516 0.449997 6.61516 1.30124C6.61516 1.63218 6.76094 1.97905 7.02601 2.28561C8.47328 4.0235 9.80875 5.9196 9.82797 8.17335C9.82797 8.62265 9.77992 9.01969 9.4682 9.5768L10.0098 9.45539C9.38969 7.63782 8.06453 6.5625 6.8814 6.5625C6.33133 6.5625 5.9568 6.94992 5.9568 7.55742C5.9568 7.91906 6.04094 8.56851 6.04094 9.14109C6.04094 11.823 4 13.2105 4 17.5317C4 22.4217 7.7439 25.6767 13.3923 25.6767ZM13.5159 23.707C9.00436 23.707 5.98139 21.222 5.98139 17.5317C5.98139 13.8335 7.99022 12.6466 7.98601 9.32343C7.98601 8.90648 7.90069 8.52796 7.81421 8.19749L7.44765 8.57765C8.35164 9.26155 8.9657 10.3931 9.30906 11.9269C9.36437 12.2383 9.54718 12.4062 9.79679 12.4062C10.8662 12.4062 11.5881 9.97475 11.5881 8.29242C11.5881 5.64984 10.4617 3.09726 8.62772 1.36382L8.19108 1.87171C16.4713 2.10655 21.8493 7.57616 21.8493 14.9611C21.8493 20.1907 18.5099 23.707 13.5159 23.707ZM13.6787 22.0695C16.3855 22.0695 17.7723 20.103 17.7723 17.7848C17.7723 15.4617 16.4301 12.9703 13.9164 11.8139C13.7751 11.764 13.6605 11.8437 13.6848 11.9955C13.8946 13.8119 13.6649 15.492 13.0649 16.3905C12.7888 15.6919 12.4478 15.1144 11.9341 14.6444C11.8162 14.5404 11.7016 14.603 11.6802 14.738C11.4991 16.1297 10.0743 16.811 10.0743 18.8292C10.0743 20.7684 11.5105 22.0695 13.6787 22.0695Z';
             break;
         case 7: // Mine
-            color = perk.owner === this.selfId ? '#6b0000' : '#f00000';
+            color = this.isAlly(perk.owner) ? '#6b0000' : '#f00000';
             icon = 'M13.3923 25.6767C19.698 25.6767 23.9031 21.4235 23.9031 15.0239C23.9031 4.42804 14.8448 0 8.59913 0C7.39843 0 6.61516 0.449997 6.61516 1.30124C6.61516 1.63218 6.76094 1.97905 7.02601 2.28561C8.47328 4.0235 9.80875 5.9196 9.82797 8.17335C9.82797 8.62265 9.77992 9.01969 9.4682 9.5768L10.0098 9.45539C9.38969 7.63782 8.06453 6.5625 6.8814 6.5625C6.33133 6.5625 5.9568 6.94992 5.9568 7.55742C5.9568 7.91906 6.04094 8.56851 6.04094 9.14109C6.04094 11.823 4 13.2105 4 17.5317C4 22.4217 7.7439 25.6767 13.3923 25.6767ZM13.5159 23.707C9.00436 23.707 5.98139 21.222 5.98139 17.5317C5.98139 13.8335 7.99022 12.6466 7.98601 9.32343C7.98601 8.90648 7.90069 8.52796 7.81421 8.19749L7.44765 8.57765C8.35164 9.26155 8.9657 10.3931 9.30906 11.9269C9.36437 12.2383 9.54718 12.4062 9.79679 12.4062C10.8662 12.4062 11.5881 9.97475 11.5881 8.29242C11.5881 5.64984 10.4617 3.09726 8.62772 1.36382L8.19108 1.87171C16.4713 2.10655 21.8493 7.57616 21.8493 14.9611C21.8493 20.1907 18.5099 23.707 13.5159 23.707ZM13.6787 22.0695C16.3855 22.0695 17.7723 20.103 17.7723 17.7848C17.7723 15.4617 16.4301 12.9703 13.9164 11.8139C13.7751 11.764 13.6605 11.8437 13.6848 11.9955C13.8946 13.8119 13.6649 15.492 13.0649 16.3905C12.7888 15.6919 12.4478 15.1144 11.9341 14.6444C11.8162 14.5404 11.7016 14.603 11.6802 14.738C11.4991 16.1297 10.0743 16.811 10.0743 18.8292C10.0743 20.7684 11.5105 22.0695 13.6787 22.0695Z';
             break;
         case 8: // Multi-snake
             color = '#5eeaf7';
             icon = 'M14.1601 25.1634C14.9092 25.1634 15.321 24.6856 15.321 23.801V17.239C15.321 14.4708 18.4571 10.5319 21.1501 8.81648L22.0961 8.205C22.4643 7.98047 22.6675 7.57711 22.6675 7.18125C22.6675 6.53532 22.2332 6.06517 21.5322 6.06517C21.2075 6.06517 20.8616 6.17696 20.5508 6.38134L19.934 6.78728C17.331 8.51908 14.63 11.8953 14.1761 13.7644H14.1367C13.6806 11.8856 10.9893 8.51908 8.38628 6.78728L7.76941 6.38134C7.45113 6.17485 7.11269 6.06306 6.78058 6.06306C6.07535 6.06306 5.65488 6.56837 5.65488 7.17165C5.65488 7.5675 5.85598 7.97625 6.22418 8.20289L7.17012 8.81648C9.84926 10.5319 13.0014 14.4708 13.0014 17.239V23.801C13.0014 24.6856 13.4132 25.1634 14.1601 25.1634ZM5.92887 10.2504L9.50121 5.63369C10.0431 4.93103 9.67394 4.32564 8.78965 4.2926L3.9695 4.09971C3.24364 4.06666 2.84357 4.57197 3.05755 5.27767L4.4495 9.88477C4.70286 10.7468 5.37528 10.9648 5.92887 10.2504ZM22.2318 10.2323C22.7598 10.9648 23.446 10.7723 23.7174 9.92203L25.2531 5.3522C25.4809 4.65822 25.0946 4.13908 24.3784 4.14658L19.5486 4.1958C18.6643 4.2033 18.2814 4.79275 18.7977 5.50923L22.2318 10.2323Z';
             break;
-        default: return;
+        case 9: // Shrink
+            color = '#8d6e63';
+            icon = 'M4 11.5H24V16.5H4Z';
+            break;
+        case 10: // Freeze
+            color = '#7ec8e3';
+            icon = 'M7 5H12V23H7Z M16 5H21V23H16Z';
+            break;
+        case 11: // Invert
+            color = '#a3c940';
+            icon = 'M2 9L8 3V7H20V11H8V15Z M26 19L20 25V21H8V17H20V13Z';
+            break;
+        case 12: // Magnet
+            color = '#b0b7c3';
+            icon = 'M5 4H11V14A3 3 0 0 0 17 14V4H23V14A9 9 0 0 1 5 14Z';
+            break;
+        case 13: // Bomb
+            color = '#3d3d3d';
+            icon = 'M14 27A10 10 0 1 1 14 7A10 10 0 1 1 14 27Z M16 8L20 2H24L19 9Z';
+            break;
+        case 14: // Swapper
+            color = '#ff8fab';
+            icon = 'M2 9L8 3V7H26V11H8V15Z M26 19L20 25V21H2V17H20V13Z';
+            break;
+        case 15: // Blackout
+            color = '#14213d';
+            icon = 'M14 3A11 11 0 1 0 25 17A8 8 0 1 1 14 3Z';
+            break;
+        case 16: // Slow others
+            color = '#6a994e';
+            icon = 'M4 20A10 10 0 0 1 24 20Z M2 22H26V25H2Z';
+            break;
+        case 17: // Reverse others
+            color = '#c9a227';
+            icon = 'M14 4A10 10 0 1 1 4 14H8A6 6 0 1 0 14 8V12L8 6L14 0Z';
+            break;
+        case 18: // Critter
+            color = '#8bc34a';
+            icon = 'M14 6C19 6 23 10 23 15C23 20 19 24 14 24C9 24 5 20 5 15C5 10 9 6 14 6Z M9 2L12 7 M19 2L16 7 M10 13A1.5 1.5 0 1 0 10 16A1.5 1.5 0 1 0 10 13Z M18 13A1.5 1.5 0 1 0 18 16A1.5 1.5 0 1 0 18 13Z';
+            break;
+        default: return null;
         }
+        return {
+            color, icon, iconWidth, iconHeight,
+        };
+    }
+
+    drawPerk(perk) {
+        const style = this.perkStyle(perk);
+        if (style === null) {
+            return;
+        }
+        const {
+            color, icon, iconWidth, iconHeight,
+        } = style;
+        // Multi-cell perks are drawn over their whole square.
+        const span = (perk.side ?? 1) * this.cellSize;
         this.context.fillStyle = color;
         this.context.beginPath();
         this.context.arc(
-            BORDER_WIDTH + perk.coord.x * this.cellSize + this.cellSize / 2,
-            BORDER_WIDTH + perk.coord.y * this.cellSize + this.cellSize / 2,
-            this.cellSize / 3,
+            this.cellLeft(perk.coord) + span / 2,
+            this.cellTop(perk.coord) + span / 2,
+            span / 3,
             0,
             2 * Math.PI,
         );
@@ -709,12 +1792,12 @@ class Game {
         if (this.cellSize >= 15) {
             this.context.fillStyle = '#ffffff';
             this.context.setTransform(
-                this.cellSize / 2 / iconWidth,
+                span / 2 / iconWidth,
                 0,
                 0,
-                this.cellSize / 2 / iconHeight,
-                BORDER_WIDTH + perk.coord.x * this.cellSize + this.cellSize / 4,
-                BORDER_WIDTH + perk.coord.y * this.cellSize + this.cellSize / 4,
+                span / 2 / iconHeight,
+                this.cellLeft(perk.coord) + span / 4,
+                this.cellTop(perk.coord) + span / 4,
             );
             this.context.fill(new Path2D(icon));
             this.context.setTransform(1, 0, 0, 1, 0, 0);